@@ -1,11 +1,20 @@
 use badargs::arg;
 
-arg!(OutFile: "output", 'o' -> String);
-arg!(Force: "force", 'f' -> bool);
-arg!(OLevel: "optimize" -> usize);
+arg!(
+    /// The file the output is written to
+    OutFile: "output", 'o' -> String
+);
+arg!(
+    /// Overwrite the output file if it exists
+    Force: "force", 'f' -> bool
+);
+arg!(
+    /// The optimization level
+    OLevel: "optimize" -> usize
+);
 
 fn main() {
-    let args = badargs::badargs!(OutFile, Force, OLevel);
+    let args = badargs::badargs!(app: badargs::app!(); OutFile, Force, OLevel);
 
     let outfile = args.get::<OutFile>();
     let force = args.get::<Force>();
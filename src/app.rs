@@ -0,0 +1,293 @@
+use crate::error::{CallError, Error, SchemaError};
+use crate::parse::CliArgs;
+use crate::reporting;
+use crate::schema::{IntoSchema, Schema};
+use crate::BadArgs;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+///
+/// Metadata about the program, shown on the help page and by `--version`, and settings for the built-in flags
+///
+/// You probably want to fill it from your `Cargo.toml` using the [`app!`](crate::app!) macro
+/// ```
+/// use badargs::{arg, App};
+///
+/// arg!(Force: "force", 'f' -> bool);
+///
+/// let args = App::new()
+///     .name("compiler")
+///     .version("1.0.0")
+///     .about("Compiles things")
+///     .badargs::<Force>();
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct App {
+    pub(crate) name: Option<&'static str>,
+    pub(crate) version: Option<&'static str>,
+    pub(crate) about: Option<&'static str>,
+    pub(crate) author: Option<&'static str>,
+    pub(crate) sort_help: bool,
+    pub(crate) help_long: Option<&'static str>,
+    pub(crate) help_short: Option<char>,
+    pub(crate) error_exit_code: i32,
+    pub(crate) help_exit_code: i32,
+    pub(crate) config_file: Option<PathBuf>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            name: None,
+            version: None,
+            about: None,
+            author: None,
+            sort_help: false,
+            help_long: Some("help"),
+            help_short: Some('h'),
+            error_exit_code: 2,
+            help_exit_code: 0,
+            config_file: None,
+        }
+    }
+}
+
+impl App {
+    /// Creates an `App` without any metadata
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The name of the program, shown by `--version`
+    ///
+    /// The usage shows the name the program was invoked with instead, and only falls back to this one
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// The version of the program, this enables the `--version` and `-V` flags
+    pub fn version(mut self, version: &'static str) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// A short description of what the program does
+    pub fn about(mut self, about: &'static str) -> Self {
+        self.about = Some(about);
+        self
+    }
+
+    /// The author of the program
+    pub fn author(mut self, author: &'static str) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// List the arguments on the help page in alphabetical order instead of the order they were declared in
+    pub fn sort_help(mut self, sort: bool) -> Self {
+        self.sort_help = sort;
+        self
+    }
+
+    /// The flags that show the help page, `--help` and `-h` by default
+    ///
+    /// Passing `None` turns the flag off. The help flags are also turned off if an argument uses
+    /// the same name
+    /// ```
+    /// use badargs::{arg, App};
+    ///
+    /// arg!(Host: "host", 'h' -> String);
+    ///
+    /// let app = App::new().help_flags(Some("usage"), None);
+    /// let args = app.try_badargs_from::<Host, _>(["ping", "-h", "localhost"]).unwrap();
+    ///
+    /// assert_eq!(args.get::<Host>(), Some(&"localhost".to_string()));
+    /// ```
+    pub fn help_flags(mut self, long: Option<&'static str>, short: Option<char>) -> Self {
+        self.help_long = long;
+        self.help_short = short;
+        self
+    }
+
+    /// The exit code used by [`badargs()`](crate::badargs()) when the arguments are invalid, `2` by default
+    pub fn error_exit_code(mut self, code: i32) -> Self {
+        self.error_exit_code = code;
+        self
+    }
+
+    /// The exit code used by [`badargs()`](crate::badargs()) after printing the help page or
+    /// the version, `0` by default
+    pub fn help_exit_code(mut self, code: i32) -> Self {
+        self.help_exit_code = code;
+        self
+    }
+
+    /// A config file with `key = value` lines that provides values for the arguments
+    ///
+    /// The keys are the long names of the arguments, and the values are parsed just like values
+    /// from the command line. Values from the command line and the environment take precedence over
+    /// the file, and the file takes precedence over default values. The arguments of subcommands are
+    /// set in a section named after them, like `[build]`. Lines starting with `#` or `;` are comments.
+    ///
+    /// The file is ignored if it does not exist
+    /// ```
+    /// use badargs::{arg, App, ValueSource};
+    ///
+    /// arg!(Jobs: "jobs", 'j' -> usize = 1);
+    /// arg!(Name: "name" -> String);
+    ///
+    /// let path = std::env::temp_dir().join("badargs-doc-example.conf");
+    /// std::fs::write(&path, "# the build settings\njobs = 4\nname = \"hello world\"\n").unwrap();
+    ///
+    /// let app = App::new().config_file(&path);
+    /// let args = app.badargs_from::<(Jobs, Name), _>(["make", "--name", "other"]);
+    ///
+    /// assert_eq!(args.value::<Jobs>(), &4);
+    /// assert_eq!(args.source::<Jobs>(), Some(ValueSource::ConfigFile { line: 2 }));
+    /// assert_eq!(args.get::<Name>(), Some(&"other".to_string()));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Renders the help page of the command reached by the `subcommands`, as it is printed for `--help`
+    ///
+    /// The subcommands of a [`CallError::HelpPage`] can be passed as they are. This is useful
+    /// together with [`try_badargs()`](crate::try_badargs()), which returns the error instead of
+    /// printing the help page
+    /// ```
+    /// use badargs::{arg, App};
+    ///
+    /// arg!(Force: "force", 'f' -> bool);
+    ///
+    /// let app = App::new().name("rm").version("1.0.0");
+    /// assert_eq!(
+    ///     app.help_page::<Force>(&[]).unwrap(),
+    ///     "rm 1.0.0\n\nUsage: rm [-f] [ARGS...]\n\nOptions:\n  -f, --force\n  -h, --help     Print help\n  -V, --version  Print version\n"
+    /// );
+    /// assert_eq!(app.usage::<Force>(&[]).unwrap(), "Usage: rm [OPTIONS] [ARGS...]");
+    /// assert_eq!(app.version_text(), "rm 1.0.0");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This function panics if one of the `subcommands` does not exist
+    pub fn help_page<S: IntoSchema>(&self, subcommands: &[&str]) -> Result<String, SchemaError> {
+        let schema = Schema::create::<S>()?;
+        Ok(reporting::help_page(&schema, self, self.name, subcommands))
+    }
+
+    /// Renders the short usage of the command reached by the `subcommands`, as it is printed below
+    /// errors
+    ///
+    /// # Panics
+    ///
+    /// This function panics if one of the `subcommands` does not exist
+    pub fn usage<S: IntoSchema>(&self, subcommands: &[&str]) -> Result<String, SchemaError> {
+        let schema = Schema::create::<S>()?;
+        Ok(reporting::command_usage(&schema, self.name, subcommands))
+    }
+
+    /// Renders the name and version of the program, as it is printed for `--version`
+    pub fn version_text(&self) -> String {
+        reporting::version(self)
+    }
+
+    ///
+    /// Parses the command line arguments based on the provided schema S
+    ///
+    /// See [`badargs()`](crate::badargs())
+    ///
+    /// # Panics
+    ///
+    /// This function panics if an invalid schema is entered
+    ///
+    pub fn badargs<S>(&self) -> BadArgs
+    where
+        S: IntoSchema,
+    {
+        self.badargs_from::<S, _>(std::env::args_os())
+    }
+
+    ///
+    /// Parses the command line arguments based on the provided schema S
+    ///
+    /// See [`try_badargs()`](crate::try_badargs())
+    ///
+    pub fn try_badargs<S>(&self) -> Result<BadArgs, Error>
+    where
+        S: IntoSchema,
+    {
+        self.try_badargs_from::<S, _>(std::env::args_os())
+    }
+
+    ///
+    /// Parses the provided arguments based on the provided schema S
+    ///
+    /// See [`badargs_from()`](crate::badargs_from())
+    ///
+    /// # Panics
+    ///
+    /// This function panics if an invalid schema is entered
+    ///
+    pub fn badargs_from<S, I>(&self, args: I) -> BadArgs
+    where
+        S: IntoSchema,
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let arg_schema = Schema::create::<S>().expect("Invalid schema");
+
+        let mut args = args.into_iter().map(Into::into);
+        let program_name = args.next().map(|name| name.to_string_lossy().into_owned());
+
+        match self.parse_args::<S>(&arg_schema, program_name.clone(), args) {
+            Ok(args) => args,
+            Err((err, subcommands)) => reporting::report(
+                err,
+                &subcommands,
+                &arg_schema,
+                self,
+                program_name.as_deref(),
+            ),
+        }
+    }
+
+    ///
+    /// Parses the provided arguments based on the provided schema S
+    ///
+    /// See [`try_badargs_from()`](crate::try_badargs_from())
+    ///
+    pub fn try_badargs_from<S, I>(&self, args: I) -> Result<BadArgs, Error>
+    where
+        S: IntoSchema,
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let arg_schema = Schema::create::<S>()?;
+
+        let mut args = args.into_iter().map(Into::into);
+        let program_name = args.next().map(|name| name.to_string_lossy().into_owned());
+
+        let args = self
+            .parse_args::<S>(&arg_schema, program_name, args)
+            .map_err(|(err, _)| err)?;
+        Ok(args)
+    }
+
+    fn parse_args<S: IntoSchema>(
+        &self,
+        schema: &Schema,
+        program_name: Option<String>,
+        args: impl Iterator<Item = OsString>,
+    ) -> Result<BadArgs, (CallError, Vec<&'static str>)> {
+        let mut args = CliArgs::from_args(schema, self, args)?;
+        if let Err(err) = S::convert(&mut args) {
+            return Err((err, args.subcommand_path()));
+        }
+        Ok(BadArgs::new(args, program_name))
+    }
+}
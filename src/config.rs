@@ -0,0 +1,266 @@
+//!
+//! Loads the values of arguments from a simple config file
+//!
+//! The file consists of `key = value` lines, where the keys are the long names of the arguments.
+//! Lines starting with `#` or `;` are comments. The arguments of subcommands are set in a section
+//! named after the subcommand, like `[build]` or `[remote.add]` for nested subcommands.
+//! Values can be quoted with `"` or `'`, and keys of arguments that can be repeated can be
+//! repeated as well.
+
+use crate::error::CallError;
+use crate::parse;
+use crate::schema::Schema;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// A loaded config file
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Config {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+/// A single `key = value` line
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Entry {
+    /// The subcommands of the section the line is in
+    pub section: Vec<String>,
+    pub key: String,
+    pub value: String,
+    /// The line number, starting at 1
+    pub line: usize,
+}
+
+impl Config {
+    /// Reads and parses the config file, returns `None` if it does not exist
+    pub fn load(path: &Path) -> Result<Option<Self>, CallError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(path, &text).map(Some),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(CallError::ConfigFile {
+                path: path.to_path_buf(),
+                line: None,
+                message: format!("could not read the file: {}", err),
+            }),
+        }
+    }
+
+    pub fn parse(path: &Path, text: &str) -> Result<Self, CallError> {
+        let error = |line: usize, message: String| CallError::ConfigFile {
+            path: path.to_path_buf(),
+            line: Some(line),
+            message,
+        };
+
+        let mut section = Vec::new();
+        let mut entries = Vec::new();
+
+        for (idx, content) in text.lines().enumerate() {
+            let line = idx + 1;
+            let content = content.trim();
+
+            if content.is_empty() || content.starts_with('#') || content.starts_with(';') {
+                continue;
+            }
+
+            if let Some(name) = content.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .ok_or_else(|| error(line, format!("expected ']' in '{}'", content)))?;
+                section = name
+                    .split('.')
+                    .map(|subcommand| subcommand.trim().to_string())
+                    .filter(|subcommand| !subcommand.is_empty())
+                    .collect();
+                continue;
+            }
+
+            let (key, value) = content
+                .split_once('=')
+                .ok_or_else(|| error(line, format!("expected 'key = value', got '{}'", content)))?;
+
+            entries.push(Entry {
+                section: section.clone(),
+                key: key.trim().to_string(),
+                value: unquote(value.trim()).to_string(),
+                line,
+            });
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Checks that every section is a subcommand, every key an argument of it, and every value valid
+    pub fn check(&self, schema: &Schema) -> Result<(), CallError> {
+        for entry in &self.entries {
+            let error = |message: String| CallError::ConfigFile {
+                path: self.path.clone(),
+                line: Some(entry.line),
+                message,
+            };
+
+            let mut section_schema = schema;
+            for subcommand in &entry.section {
+                section_schema = &section_schema
+                    .subcommand(subcommand)
+                    .ok_or_else(|| error(format!("subcommand '{}' does not exist", subcommand)))?
+                    .schema;
+            }
+
+            let command = section_schema
+                .long(&entry.key)
+                .ok_or_else(|| error(format!("argument '{}' does not exist", entry.key)))?;
+
+            parse::check_setting(command, &entry.value).map_err(|err| CallError::FromConfig {
+                path: self.path.clone(),
+                line: entry.line,
+                err: Box::new(err),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// The entries for the argument `long` of the subcommand reached by `section`, in the order of the file
+    pub fn entries<'a>(
+        &'a self,
+        section: &'a [&str],
+        long: &'a str,
+    ) -> impl Iterator<Item = &'a Entry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.key == long && entry.section == section)
+    }
+}
+
+/// Removes the quotes around a value, if there are any
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''].iter() {
+        if let Some(unquoted) = value
+            .strip_prefix(*quote)
+            .and_then(|value| value.strip_suffix(*quote))
+        {
+            return unquoted;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{arg, command, Subcommands};
+
+    arg!(Jobs: "jobs", 'j' -> usize);
+    arg!(Name: "name" -> String);
+    arg!(Release: "release" -> bool);
+
+    command!(Build: "build" -> Release);
+
+    fn parse(text: &str) -> Result<Config, CallError> {
+        Config::parse(Path::new("app.conf"), text)
+    }
+
+    fn schema() -> Schema {
+        Schema::create::<(Jobs, (Name, Subcommands<Build>))>().unwrap()
+    }
+
+    #[test]
+    fn entries() {
+        let config = parse(
+            "# comment\n\
+             jobs = 4\n\
+             \n\
+             ; another comment\n\
+             name = \"hello world\"\n\
+             [build]\n\
+             release=true\n",
+        )
+        .unwrap();
+
+        let jobs = config.entries(&[], "jobs").collect::<Vec<_>>();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].value, "4");
+        assert_eq!(jobs[0].line, 2);
+
+        let name = config.entries(&[], "name").next().unwrap();
+        assert_eq!(name.value, "hello world");
+
+        assert_eq!(config.entries(&[], "release").count(), 0);
+        let release = config.entries(&["build"], "release").next().unwrap();
+        assert_eq!(release.value, "true");
+        assert_eq!(release.line, 7);
+
+        assert_eq!(config.check(&schema()), Ok(()));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let err = parse("jobs = 4\njobs").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::ConfigFile {
+                path: PathBuf::from("app.conf"),
+                line: Some(2),
+                message: "expected 'key = value', got 'jobs'".to_string(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "app.conf:2: expected 'key = value', got 'jobs'."
+        );
+
+        assert!(parse("[build").is_err());
+    }
+
+    #[test]
+    fn check_errors() {
+        let err = parse("jbos = 4").unwrap().check(&schema()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "app.conf:1: argument 'jbos' does not exist."
+        );
+
+        let err = parse("[test]\njobs = 4")
+            .unwrap()
+            .check(&schema())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "app.conf:2: subcommand 'test' does not exist."
+        );
+
+        let err = parse("\njobs = many")
+            .unwrap()
+            .check(&schema())
+            .unwrap_err();
+        assert_eq!(
+            err,
+            CallError::FromConfig {
+                path: PathBuf::from("app.conf"),
+                line: 2,
+                err: Box::new(CallError::InvalidValue {
+                    long: "jobs",
+                    value: "many".to_string(),
+                    kind: crate::schema::SchemaKind::UInt,
+                }),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "app.conf:2: argument '--jobs' expected positive integer value, but got 'many'."
+        );
+    }
+
+    #[test]
+    fn missing_file() {
+        let path = std::env::temp_dir().join("badargs-test-missing.conf");
+        assert_eq!(Config::load(&path), Ok(None));
+    }
+}
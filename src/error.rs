@@ -0,0 +1,369 @@
+use crate::schema::SchemaKind;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+/// Any error that can happen while parsing the arguments
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The schema provided by the program is invalid
+    Schema(SchemaError),
+    /// The arguments provided by the user are invalid
+    Call(CallError),
+}
+
+/// Invalid schema
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SchemaError {
+    /// The argument name was already provided for a different argument
+    NameAlreadyExists(String),
+    /// The arguments don't fit together, like an invalid default value, a positional argument
+    /// without a value or positional arguments next to subcommands
+    InvalidSchema(String),
+}
+
+/// Invalid arguments provided
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CallError {
+    /// A short flag that is not part of the schema, like the `x` in `-fx`
+    ShortFlagNotFound {
+        flag: char,
+        token: String,
+        /// Flags that the user might have meant instead
+        suggestions: Vec<String>,
+    },
+    /// A long flag that is not part of the schema
+    LongFlagNotFound {
+        flag: String,
+        token: String,
+        /// Flags that the user might have meant instead
+        suggestions: Vec<String>,
+    },
+    /// The argument takes a value, but there was none left
+    ExpectedValue {
+        long: &'static str,
+        kind: SchemaKind,
+    },
+    /// A value was given to a flag that does not take one, like `--force=yes`
+    UnexpectedValue { long: &'static str, value: String },
+    /// The value could not be parsed into the type of the argument
+    InvalidValue {
+        long: &'static str,
+        value: String,
+        kind: SchemaKind,
+    },
+    /// The value was rejected by the `FromStr` implementation of a [`CliValue`](crate::CliValue) type
+    ParseFailed {
+        long: &'static str,
+        value: String,
+        /// The error returned by `from_str`
+        message: String,
+    },
+    /// Required arguments were not provided
+    MissingRequired {
+        /// The long names of the missing flags
+        longs: Vec<&'static str>,
+        /// The positional arguments that did not get enough operands
+        positionals: Vec<&'static str>,
+    },
+    /// There were more operands than the positional arguments take
+    UnexpectedPositional { value: String },
+    /// The operand could not be parsed into the type of the positional argument
+    InvalidPositional {
+        name: &'static str,
+        value: String,
+        /// Why the value is invalid
+        reason: String,
+    },
+    /// An argument was not valid UTF-8
+    InvalidUtf8(OsString),
+    /// The value of an environment variable that an argument falls back to is invalid
+    FromEnv {
+        var: &'static str,
+        /// The error about the value
+        err: Box<CallError>,
+    },
+    /// The config file could not be read, or contains something other than arguments
+    ConfigFile {
+        path: PathBuf,
+        /// The line number, starting at 1
+        line: Option<usize>,
+        message: String,
+    },
+    /// The value of an argument in the config file is invalid
+    FromConfig {
+        path: PathBuf,
+        /// The line number, starting at 1
+        line: usize,
+        /// The error about the value
+        err: Box<CallError>,
+    },
+    /// A subcommand that is not part of the schema
+    SubcommandNotFound {
+        name: String,
+        /// Subcommands that the user might have meant instead
+        suggestions: Vec<String>,
+    },
+    /// `--help` was passed, after the subcommands that it should be shown for
+    HelpPage(Vec<&'static str>),
+    /// `--version` was passed
+    Version,
+}
+
+impl CallError {
+    /// The flag that caused the error, as it would be written on the command line
+    pub fn flag(&self) -> Option<String> {
+        match self {
+            CallError::ShortFlagNotFound { flag, .. } => Some(format!("-{}", flag)),
+            CallError::LongFlagNotFound { flag, .. } => Some(format!("--{}", flag)),
+            CallError::ExpectedValue { long, .. }
+            | CallError::UnexpectedValue { long, .. }
+            | CallError::InvalidValue { long, .. }
+            | CallError::ParseFailed { long, .. } => Some(format!("--{}", long)),
+            CallError::MissingRequired { longs, .. } => {
+                longs.first().map(|long| format!("--{}", long))
+            }
+            CallError::FromEnv { err, .. } | CallError::FromConfig { err, .. } => err.flag(),
+            CallError::ConfigFile { .. } => None,
+            CallError::InvalidUtf8(_)
+            | CallError::UnexpectedPositional { .. }
+            | CallError::InvalidPositional { .. }
+            | CallError::SubcommandNotFound { .. }
+            | CallError::HelpPage(_)
+            | CallError::Version => None,
+        }
+    }
+
+    /// The raw token that caused the error
+    ///
+    /// For unknown flags this is the whole token, for invalid values it's only the value
+    pub fn token(&self) -> Option<&OsStr> {
+        match self {
+            CallError::ShortFlagNotFound { token, .. }
+            | CallError::LongFlagNotFound { token, .. } => Some(token.as_ref()),
+            CallError::UnexpectedValue { value, .. }
+            | CallError::InvalidValue { value, .. }
+            | CallError::ParseFailed { value, .. }
+            | CallError::UnexpectedPositional { value }
+            | CallError::InvalidPositional { value, .. } => Some(value.as_ref()),
+            CallError::InvalidUtf8(token) => Some(token),
+            CallError::FromEnv { err, .. } | CallError::FromConfig { err, .. } => err.token(),
+            CallError::ConfigFile { .. } => None,
+            CallError::SubcommandNotFound { name, .. } => Some(name.as_ref()),
+            CallError::ExpectedValue { .. }
+            | CallError::MissingRequired { .. }
+            | CallError::HelpPage(_)
+            | CallError::Version => None,
+        }
+    }
+
+    /// Turns an error about the value of the argument `name` into one about a positional argument
+    pub(crate) fn for_positional(self, name: &'static str) -> CallError {
+        match self {
+            CallError::InvalidValue { value, kind, .. } => CallError::InvalidPositional {
+                name,
+                value,
+                reason: format!("expected {}", expected(kind)),
+            },
+            CallError::ParseFailed { value, message, .. } => CallError::InvalidPositional {
+                name,
+                value,
+                reason: message,
+            },
+            err => err,
+        }
+    }
+}
+
+/// Describes what kind of value was expected, like `integer value` or `one of 'a', 'b'`
+fn expected(kind: SchemaKind) -> String {
+    let name = match kind {
+        SchemaKind::String | SchemaKind::OsString => "string",
+        SchemaKind::Path => "path",
+        SchemaKind::Bool => "boolean",
+        SchemaKind::Count => "count",
+        SchemaKind::IInt => "integer",
+        SchemaKind::UInt => "positive integer",
+        SchemaKind::Num => "number",
+        SchemaKind::Vec(inner) => return expected(*inner),
+        SchemaKind::Custom {
+            choices: Some(choices),
+            ..
+        } => {
+            let choices = choices
+                .iter()
+                .map(|choice| format!("'{}'", choice))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return format!("one of {}", choices);
+        }
+        SchemaKind::Custom { name, .. } => return name.to_string(),
+    };
+    format!("{} value", name)
+}
+
+fn write_suggestions(f: &mut Formatter<'_>, suggestions: &[String]) -> std::fmt::Result {
+    match suggestions {
+        [] => write!(f, "."),
+        [single] => write!(f, ", did you mean '{}'?", single),
+        [rest @ .., last] => {
+            write!(f, ", did you mean ")?;
+            for suggestion in rest {
+                write!(f, "'{}', ", suggestion)?;
+            }
+            write!(f, "or '{}'?", last)
+        }
+    }
+}
+
+impl Display for CallError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallError::ShortFlagNotFound {
+                flag, suggestions, ..
+            } => {
+                write!(f, "argument '-{}' does not exist", flag)?;
+                write_suggestions(f, suggestions)
+            }
+            CallError::LongFlagNotFound {
+                flag, suggestions, ..
+            } => {
+                write!(f, "argument '--{}' does not exist", flag)?;
+                write_suggestions(f, suggestions)
+            }
+            CallError::ExpectedValue { long, kind } => write!(
+                f,
+                "argument '--{}' expected {}, but got nothing.",
+                long,
+                expected(*kind)
+            ),
+            CallError::UnexpectedValue { long, value } => write!(
+                f,
+                "argument '--{}' does not take a value, but got '{}'.",
+                long, value
+            ),
+            CallError::InvalidValue { long, value, kind } => write!(
+                f,
+                "argument '--{}' expected {}, but got '{}'.",
+                long,
+                expected(*kind),
+                value
+            ),
+            CallError::ParseFailed {
+                long,
+                value,
+                message,
+            } => write!(
+                f,
+                "argument '--{}' got invalid value '{}': {}.",
+                long,
+                value,
+                message.trim_end_matches('.')
+            ),
+            CallError::MissingRequired { longs, positionals } => {
+                let names = longs
+                    .iter()
+                    .map(|long| format!("'--{}'", long))
+                    .chain(positionals.iter().map(|name| format!("'<{}>'", name)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "missing required arguments: {}.", names)
+            }
+            CallError::UnexpectedPositional { value } => {
+                write!(f, "unexpected argument '{}'.", value)
+            }
+            CallError::InvalidPositional {
+                name,
+                value,
+                reason,
+            } => write!(
+                f,
+                "argument '<{}>' got invalid value '{}': {}.",
+                name,
+                value,
+                reason.trim_end_matches('.')
+            ),
+            CallError::FromEnv { var, err } => {
+                write!(f, "environment variable '{}' is invalid: {}", var, err)
+            }
+            CallError::ConfigFile {
+                path,
+                line,
+                message,
+            } => {
+                write!(f, "{}", path.display())?;
+                if let Some(line) = line {
+                    write!(f, ":{}", line)?;
+                }
+                write!(f, ": {}.", message)
+            }
+            CallError::FromConfig { path, line, err } => {
+                write!(f, "{}:{}: {}", path.display(), line, err)
+            }
+            CallError::InvalidUtf8(os_str) => {
+                write!(f, "invalid utf8: '{}'", os_str.to_string_lossy())
+            }
+            CallError::SubcommandNotFound { name, suggestions } => {
+                write!(f, "subcommand '{}' does not exist", name)?;
+                write_suggestions(f, suggestions)
+            }
+            CallError::HelpPage(_) => write!(f, "the help page was requested"),
+            CallError::Version => write!(f, "the version was requested"),
+        }
+    }
+}
+
+impl std::error::Error for CallError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CallError::FromEnv { err, .. } | CallError::FromConfig { err, .. } => Some(&**err),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaError::NameAlreadyExists(name) => {
+                write!(f, "the argument name '{}' is used more than once", name)
+            }
+            SchemaError::InvalidSchema(msg) => write!(f, "invalid schema: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Schema(err) => Display::fmt(err, f),
+            Error::Call(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+// the inner error is displayed as it is, so its source is the source of this error as well
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Schema(err) => err.source(),
+            Error::Call(err) => err.source(),
+        }
+    }
+}
+
+impl From<SchemaError> for Error {
+    fn from(err: SchemaError) -> Self {
+        Error::Schema(err)
+    }
+}
+
+impl From<CallError> for Error {
+    fn from(err: CallError) -> Self {
+        Error::Call(err)
+    }
+}
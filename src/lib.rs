@@ -23,6 +23,21 @@
 //!
 //! You can also invoke the [`badargs()`] function directly
 //!
+//! Metadata about the program, like its version, is provided using an [`App`]. This also enables
+//! the `--version` flag
+//! ```
+//! use badargs::arg;
+//!
+//! arg!(Force: "force", 'f' -> bool);
+//!
+//! let args = badargs::badargs!(app: badargs::app!(); Force);
+//! ```
+//!
+//! If you want to handle errors yourself instead of exiting the program, use [`try_badargs()`]
+//!
+//! To parse something other than the command line of the process, like in tests, use [`badargs_from()`]
+//! or [`try_badargs_from()`]
+//!
 //! Getting the values is done using the [`BadArgs::get`] function
 //! ```
 //! use badargs::arg;
@@ -34,24 +49,57 @@
 //! let force: Option<&bool> = args.get::<Force>();
 //! let out_file = args.get::<OutFile>();
 //! ```
+//!
+//! Positional arguments are declared using [`positional!`] and added to the arguments using [`Positionals`]
+//!
+//! Subcommands are declared using [`command!`] and added to the arguments using [`Subcommands`]
+//!
+//! Arguments can have a default value, their value can then be accessed using [`BadArgs::value`]
+//! ```
+//! use badargs::arg;
+//! arg!(Jobs: "jobs", 'j' -> usize = 4);
+//!
+//! let args = badargs::badargs!(Jobs);
+//!
+//! let jobs: &usize = args.value::<Jobs>();
+//! ```
 
+mod app;
+mod config;
+mod error;
 mod macros;
 mod parse;
 mod reporting;
 mod schema;
+mod suggestions;
 
 use crate::parse::CliArgs;
-use crate::schema::{IntoSchema, Schema, SchemaKind};
+use crate::schema::IntoSchema;
 use std::any::Any;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+    NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+use std::path::PathBuf;
+use std::str::FromStr;
 
-pub use error::SchemaError;
-pub use macros::*;
+pub use app::App;
+pub use error::{CallError, Error, SchemaError};
+pub use schema::SchemaKind;
 
 pub type Result<T> = std::result::Result<T, SchemaError>;
 
 ///
 /// Parses the command line arguments based on the provided schema S
 ///
+/// If the arguments are invalid, an error is printed to stderr and the process exits with code `2`.
+/// The help page and version are printed to stdout and the process exits with code `0`.
+/// These exit codes can be changed using an [`App`].
+///
 /// # Panics
 ///
 /// This function panics if an invalid schema is entered
@@ -60,13 +108,87 @@ pub fn badargs<S>() -> BadArgs
 where
     S: IntoSchema,
 {
-    let arg_schema = Schema::create::<S>().expect("Invalid schema");
+    badargs_from::<S, _>(std::env::args_os())
+}
 
-    let args = CliArgs::from_args(&arg_schema, std::env::args_os());
-    match args {
-        Ok(args) => BadArgs { args },
-        Err(err) => reporting::report(err, &arg_schema),
-    }
+///
+/// Parses the command line arguments based on the provided schema S
+///
+/// Unlike [`badargs()`], this function never panics or exits the process, it returns the error instead.
+/// Note that `--help` is returned as [`CallError::HelpPage`] and `--version` as [`CallError::Version`].
+/// The texts that [`badargs()`] would print can be rendered using [`App::help_page`],
+/// [`App::version_text`] and [`App::usage`]
+/// ```
+/// use badargs::{arg, App, CallError, Error};
+///
+/// arg!(Force: "force", 'f' -> bool);
+///
+/// let app = App::new().name("rm").version("1.0.0");
+/// match app.try_badargs_from::<Force, _>(["rm", "--help"]) {
+///     Ok(args) => println!("force: {:?}", args.get::<Force>()),
+///     Err(Error::Call(CallError::HelpPage(subcommands))) => {
+///         print!("{}", app.help_page::<Force>(&subcommands).unwrap())
+///     }
+///     Err(Error::Call(CallError::Version)) => println!("{}", app.version_text()),
+///     Err(err) => {
+///         eprintln!("error: {}", err);
+///         eprintln!("{}", app.usage::<Force>(&[]).unwrap());
+///     }
+/// }
+/// ```
+///
+pub fn try_badargs<S>() -> std::result::Result<BadArgs, Error>
+where
+    S: IntoSchema,
+{
+    try_badargs_from::<S, _>(std::env::args_os())
+}
+
+///
+/// Parses the provided arguments based on the provided schema S
+///
+/// Just like the command line, the first argument is the name of the program.
+/// Otherwise, this behaves like [`badargs()`]
+///
+/// # Panics
+///
+/// This function panics if an invalid schema is entered
+///
+pub fn badargs_from<S, I>(args: I) -> BadArgs
+where
+    S: IntoSchema,
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    App::new().badargs_from::<S, I>(args)
+}
+
+///
+/// Parses the provided arguments based on the provided schema S
+///
+/// Just like the command line, the first argument is the name of the program.
+/// Otherwise, this behaves like [`try_badargs()`]
+///
+/// ```
+/// use badargs::arg;
+///
+/// arg!(Force: "force", 'f' -> bool);
+/// arg!(OutFile: "output", 'o' -> String);
+///
+/// let args = badargs::try_badargs_from::<(Force, OutFile), _>(["compiler", "-o", "main", "main.c"]).unwrap();
+///
+/// assert_eq!(args.program_name(), Some("compiler"));
+/// assert_eq!(args.get::<OutFile>(), Some(&"main".to_string()));
+/// assert_eq!(args.get::<Force>(), None);
+/// assert_eq!(args.unnamed(), &["main.c"]);
+/// ```
+pub fn try_badargs_from<S, I>(args: I) -> std::result::Result<BadArgs, Error>
+where
+    S: IntoSchema,
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    App::new().try_badargs_from::<S, I>(args)
 }
 
 ///
@@ -80,15 +202,202 @@ pub trait CliArg: Any {
 
     fn long() -> &'static str;
     fn short() -> Option<char>;
+
+    /// The value used when the argument is not provided
+    ///
+    /// It is parsed just like a value from the command line
+    fn default_value() -> Option<&'static str> {
+        None
+    }
+
+    /// The description shown in the help page
+    fn description() -> Option<&'static str> {
+        None
+    }
+
+    /// Whether parsing fails if the argument is not provided
+    fn required() -> bool {
+        false
+    }
+
+    /// The environment variable that is used when the argument is not provided
+    ///
+    /// It takes precedence over the default value and is parsed just like a value from the command line
+    fn env() -> Option<&'static str> {
+        None
+    }
+}
+
+///
+/// Implemented by arguments that always have a value after parsing, which are arguments with a default
+/// value and required arguments
+///
+/// This allows getting the value using [`BadArgs::value`] without an `Option`
+pub trait AlwaysPresent: CliArg {}
+
+///
+/// Implemented by a user provided type that describes a subcommand, like the `build` in `cargo build`
+///
+/// This is mostly done using unit structs and the [`command!`] macro. The subcommands of a command
+/// are listed using [`Subcommands`] in its arguments
+pub trait CliCommand: Any {
+    /// The arguments of the subcommand, which can include further [`Subcommands`]
+    type Args: IntoSchema;
+
+    fn name() -> &'static str;
+
+    /// The description shown in the help page
+    fn description() -> Option<&'static str> {
+        None
+    }
+}
+
+///
+/// Adds the subcommands `C` to the arguments, `C` is a [`CliCommand`] or nested tuples of them
+///
+/// The arguments next to it are global, they can be used before and after the subcommand
+/// ```
+/// use badargs::{arg, command, Subcommands};
+///
+/// arg!(Verbose: "verbose", 'v' -> bool);
+/// arg!(Release: "release" -> bool);
+/// arg!(Jobs: "jobs", 'j' -> usize = 1);
+///
+/// command!(
+///     /// Compile the project
+///     Build: "build" -> Release
+/// );
+/// command!(
+///     /// Run the tests
+///     Test: "test" -> (Release, Jobs)
+/// );
+///
+/// let args = badargs::badargs_from::<(Verbose, Subcommands<(Build, Test)>), _>(
+///     ["cargo", "test", "-j4", "--verbose"],
+/// );
+///
+/// assert_eq!(args.get::<Verbose>(), Some(&true));
+/// assert_eq!(args.subcommand_name(), Some("test"));
+/// assert!(args.subcommand::<Build>().is_none());
+///
+/// let test = args.subcommand::<Test>().unwrap();
+/// assert_eq!(test.value::<Jobs>(), &4);
+/// ```
+pub struct Subcommands<C>(PhantomData<C>);
+
+///
+/// Implemented by a user provided type that describes a positional argument, an operand that is not
+/// a flag like the `main.c` in `cc main.c`
+///
+/// This is mostly done using unit structs and the [`positional!`] macro. Positional arguments are
+/// added to the arguments using [`Positionals`]
+pub trait CliPositional: Any {
+    /// The type of the value, this has to be a `Vec` for [`Arity::OneOrMore`]
+    type Content: CliReturnValue;
+
+    fn name() -> &'static str;
+
+    /// How many operands the positional argument takes
+    fn arity() -> Arity {
+        Arity::One
+    }
+
+    /// The description shown in the help page
+    fn description() -> Option<&'static str> {
+        None
+    }
+}
+
+/// How many operands a positional argument takes
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Arity {
+    /// Exactly one operand
+    One,
+    /// One operand if there are enough of them
+    Optional,
+    /// At least one operand, collected into a `Vec`
+    OneOrMore,
+}
+
+///
+/// Adds the positional arguments `P` to the arguments, `P` is a [`CliPositional`] or nested tuples of them
+///
+/// The operands are assigned to the positional arguments in the order they were declared in.
+/// Missing and extra operands are reported as errors
+/// ```
+/// use badargs::{arg, positional, Positionals};
+/// use std::path::PathBuf;
+///
+/// arg!(Force: "force", 'f' -> bool);
+/// positional!(
+///     /// The files to copy
+///     Sources: "source" -> Vec<PathBuf>, many
+/// );
+/// positional!(Dest: "dest" -> PathBuf);
+///
+/// let args = badargs::badargs_from::<(Force, Positionals<(Sources, Dest)>), _>(
+///     ["cp", "a.txt", "-f", "b.txt", "backup"],
+/// );
+///
+/// assert_eq!(
+///     args.positional::<Sources>(),
+///     Some(&vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")])
+/// );
+/// assert_eq!(args.positional::<Dest>(), Some(&PathBuf::from("backup")));
+///
+/// let err = badargs::try_badargs_from::<(Force, Positionals<(Sources, Dest)>), _>(["cp", "a.txt"])
+///     .unwrap_err();
+/// assert_eq!(err.to_string(), "missing required arguments: '<dest>'.");
+/// ```
+pub struct Positionals<P>(PhantomData<P>);
+
+/// Where the value of an argument came from
+///
+/// ```
+/// use badargs::{arg, ValueSource};
+///
+/// arg!(Jobs: "jobs", 'j' -> usize = 1);
+/// arg!(Force: "force", 'f' -> bool);
+///
+/// let args = badargs::badargs_from::<(Jobs, Force), _>(["make", "-f"]);
+///
+/// assert_eq!(args.source::<Force>(), Some(ValueSource::CommandLine));
+/// assert_eq!(args.source::<Jobs>(), Some(ValueSource::Default));
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ValueSource {
+    /// The value was given on the command line
+    CommandLine,
+    /// The value came from this environment variable
+    Environment(&'static str),
+    /// The value came from this line of the config file, see [`App::config_file`]
+    ConfigFile { line: usize },
+    /// The argument was not provided, so its default value was used
+    Default,
 }
 
 /// The struct containing parsed argument information
 #[derive(Debug, Default)]
 pub struct BadArgs {
     args: CliArgs,
+    program_name: Option<String>,
+    /// The name and arguments of the subcommand that was chosen
+    subcommand: Option<(&'static str, Box<BadArgs>)>,
 }
 
 impl BadArgs {
+    fn new(mut args: CliArgs, program_name: Option<String>) -> Self {
+        let subcommand = args.take_subcommand().map(|(name, args)| {
+            let args = BadArgs::new(args, program_name.clone());
+            (name, Box::new(args))
+        });
+        Self {
+            args,
+            program_name,
+            subcommand,
+        }
+    }
+
     /// Get the content of an argument by providing the type of the argument
     pub fn get<T>(&self) -> Option<&T::Content>
     where
@@ -98,68 +407,351 @@ impl BadArgs {
         self.args.get::<T::Content>(long_name)
     }
 
+    /// Get the content of an argument that always has a value, like an argument with a default value
+    /// or a required argument
+    ///
+    /// ```
+    /// use badargs::arg;
+    ///
+    /// arg!(Jobs: "jobs", 'j' -> usize = 4);
+    ///
+    /// let args = badargs::badargs_from::<Jobs, _>(["make"]);
+    ///
+    /// assert_eq!(args.value::<Jobs>(), &4);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `T` is not part of the schema of this command. The arguments of a
+    /// subcommand have to be read through [`BadArgs::subcommand`], not on the outer arguments
+    pub fn value<T>(&self) -> &T::Content
+    where
+        T: AlwaysPresent,
+    {
+        self.get::<T>()
+            .expect("argument that is always present was not present")
+    }
+
+    /// Get where the value of an argument came from
+    ///
+    /// Returns `None` if the argument has no value
+    pub fn source<T>(&self) -> Option<ValueSource>
+    where
+        T: CliArg,
+    {
+        self.args.source(T::long())
+    }
+
+    /// Get the value of a positional argument by providing its type
+    pub fn positional<P>(&self) -> Option<&P::Content>
+    where
+        P: CliPositional,
+    {
+        self.args.get::<P::Content>(P::name())
+    }
+
+    /// Get the name the program was invoked with, the first element of the command line
+    ///
+    /// Returns `None` if the operating system did not provide one
+    pub fn program_name(&self) -> Option<&str> {
+        self.program_name.as_deref()
+    }
+
     /// Get all unnamed additional arguments
+    ///
+    /// This includes the arguments after a `--`. Invalid UTF-8 is replaced with `U+FFFD`, use
+    /// [`BadArgs::unnamed_os`] to get the arguments as they were passed
     pub fn unnamed(&self) -> &[String] {
         self.args.unnamed()
     }
+
+    /// Get all unnamed additional arguments, without requiring them to be valid UTF-8
+    ///
+    /// ```
+    /// use badargs::arg;
+    ///
+    /// arg!(Force: "force", 'f' -> bool);
+    ///
+    /// let args = badargs::badargs_from::<Force, _>(["rm", "-f", "file.txt"]);
+    /// assert_eq!(args.unnamed_os(), &["file.txt"]);
+    /// ```
+    pub fn unnamed_os(&self) -> &[OsString] {
+        self.args.unnamed_os()
+    }
+
+    /// Get the unnamed arguments that were given before a `--`
+    ///
+    /// If there was no `--`, this is the same as [`BadArgs::unnamed`]
+    pub fn leading(&self) -> &[String] {
+        self.args.leading()
+    }
+
+    /// Get the arguments that were given after a `--`
+    ///
+    /// Returns `None` if there was no `--`. Invalid UTF-8 is replaced, use
+    /// [`BadArgs::trailing_os`] to pass the arguments on to another program unchanged
+    pub fn trailing(&self) -> Option<&[String]> {
+        self.args.trailing()
+    }
+
+    /// Like [`BadArgs::leading`], but without requiring the arguments to be valid UTF-8
+    pub fn leading_os(&self) -> &[OsString] {
+        self.args.leading_os()
+    }
+
+    /// Like [`BadArgs::trailing`], but without requiring the arguments to be valid UTF-8
+    ///
+    /// ```
+    /// use badargs::arg;
+    ///
+    /// arg!(Release: "release" -> bool);
+    ///
+    /// let args = badargs::badargs_from::<Release, _>(["run", "--release", "--", "--verbose"]);
+    /// let child_args = args.trailing_os().unwrap_or_default();
+    /// assert_eq!(child_args, &["--verbose"]);
+    /// ```
+    pub fn trailing_os(&self) -> Option<&[OsString]> {
+        self.args.trailing_os()
+    }
+
+    /// Get the name of the subcommand that was chosen
+    ///
+    /// Returns `None` if no subcommand was given
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|(name, _)| *name)
+    }
+
+    /// Get the arguments of the subcommand `C`
+    ///
+    /// Returns `None` if a different or no subcommand was chosen
+    pub fn subcommand<C>(&self) -> Option<&BadArgs>
+    where
+        C: CliCommand,
+    {
+        match &self.subcommand {
+            Some((name, args)) if *name == C::name() => Some(args),
+            _ => None,
+        }
+    }
 }
 
+///
+/// The number of times a flag was given
+///
+/// ```
+/// use badargs::{arg, Count};
+///
+/// arg!(Verbose: "verbose", 'v' -> Count);
+///
+/// let args = badargs::badargs_from::<Verbose, _>(["program", "-vv", "--verbose"]);
+///
+/// assert_eq!(args.get::<Verbose>(), Some(&Count(3)));
+/// ```
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Count(pub usize);
+
 ///
 /// A type that could be parsed from command line arguments
 pub trait CliReturnValue: sealed::SealedCliReturnValue {
-    fn kind() -> schema::SchemaKind;
+    const KIND: SchemaKind;
+}
+
+///
+/// A type that can be used as a single value of an argument, in addition to the built-in types
+///
+/// The other integer types, `f32`, `char`, the `NonZero` integers and the IP and socket addresses
+/// of `std` already implement it. Types from your own crate that implement [`FromStr`] can opt in
+/// with an empty impl. The error returned by `from_str` is shown to the user when the value is
+/// invalid. The value can also be collected into a `Vec`.
+/// ```
+/// use badargs::{arg, CliValue};
+/// use std::str::FromStr;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Level(u8);
+///
+/// impl FromStr for Level {
+///     type Err = String;
+///
+///     fn from_str(s: &str) -> Result<Self, Self::Err> {
+///         match s.parse() {
+///             Ok(level @ 0..=3) => Ok(Level(level)),
+///             _ => Err("the level must be between 0 and 3".to_string()),
+///         }
+///     }
+/// }
+///
+/// impl CliValue for Level {
+///     const NAME: &'static str = "level";
+/// }
+///
+/// arg!(OLevel: "optimize", 'O' -> Level);
+///
+/// let args = badargs::badargs_from::<OLevel, _>(["compiler", "-O", "2"]);
+/// assert_eq!(args.get::<OLevel>(), Some(&Level(2)));
+///
+/// let err = badargs::try_badargs_from::<OLevel, _>(["compiler", "-O", "4"]).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "argument '--optimize' got invalid value '4': the level must be between 0 and 3."
+/// );
+/// ```
+///
+/// The [`choice!`] macro implements this trait for enums
+pub trait CliValue: FromStr<Err: Display> + Any {
+    /// The name of the value shown on the help page, like `<value>`
+    const NAME: &'static str = "value";
+    /// The only values that are allowed, they are shown on the help page and in errors
+    const CHOICES: Option<&'static [&'static str]> = None;
+}
+
+macro_rules! impl_cli_value {
+    ($($name:literal => $($ty:ty),+;)+) => {$($(
+        impl CliValue for $ty {
+            const NAME: &'static str = $name;
+        }
+    )+)+};
 }
 
+// the types of `std` can't opt in outside of this crate
+impl_cli_value!(
+    "uint" => u8, u16, u32, u64, u128;
+    "int" => i8, i16, i32, i64, i128;
+    "uint" => NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU128, NonZeroUsize;
+    "int" => NonZeroI8, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI128, NonZeroIsize;
+    "number" => f32;
+    "char" => char;
+    "ip" => IpAddr, Ipv4Addr, Ipv6Addr;
+    "addr" => SocketAddr, SocketAddrV4, SocketAddrV6;
+);
+
 macro_rules! impl_cli_return {
     ($(for $ty:ty => $type:ident);+;) => {$(
         impl CliReturnValue for $ty {
-            fn kind() -> SchemaKind {
-                SchemaKind::$type
-            }
+            const KIND: SchemaKind = SchemaKind::$type;
+        }
+    )+};
+}
+
+macro_rules! impl_cli_return_vec {
+    ($(for $ty:ty => $type:ident);+;) => {$(
+        impl CliReturnValue for Vec<$ty> {
+            const KIND: SchemaKind = SchemaKind::Vec(&SchemaKind::$type);
         }
     )+};
 }
 
 impl_cli_return!(
     for String => String;
+    for OsString => OsString;
+    for PathBuf => Path;
     for bool => Bool;
     for isize => IInt;
     for usize => UInt;
     for f64 => Num;
+    for Count => Count;
+);
+
+// collect all occurrences of an argument
+impl_cli_return_vec!(
+    for String => String;
+    for OsString => OsString;
+    for PathBuf => Path;
+    for isize => IInt;
+    for usize => UInt;
+    for f64 => Num;
 );
 
+impl<T: CliValue> CliReturnValue for T {
+    const KIND: SchemaKind = SchemaKind::Custom {
+        name: T::NAME,
+        choices: T::CHOICES,
+    };
+}
+
+impl<T: CliValue> CliReturnValue for Vec<T> {
+    const KIND: SchemaKind = SchemaKind::Vec(&<T as CliReturnValue>::KIND);
+}
+
 mod sealed {
-    pub trait SealedCliReturnValue {}
+    use crate::{CallError, CliValue};
+    use std::any::Any;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    pub trait SealedCliReturnValue {
+        /// Converts the value stored while parsing into the final value
+        ///
+        /// Values of built-in types are already stored with their final type, values of
+        /// `CliValue` types are stored as strings
+        fn convert(_long: &'static str, raw: Box<dyn Any>) -> Result<Box<dyn Any>, CallError> {
+            Ok(raw)
+        }
+
+        /// Checks whether the default value can be parsed
+        ///
+        /// The default values of built-in types are already checked by the schema
+        fn check_default(_long: &'static str, _default: &str) -> Result<(), CallError> {
+            Ok(())
+        }
+    }
     macro_rules! impl_ {
         ($($name:ty),+) => {$(impl SealedCliReturnValue for $name{})+};
     }
-    impl_!(String, bool, usize, isize, f64);
-}
+    impl_!(
+        String,
+        OsString,
+        PathBuf,
+        bool,
+        usize,
+        isize,
+        f64,
+        crate::Count
+    );
+    impl_!(
+        Vec<String>,
+        Vec<OsString>,
+        Vec<PathBuf>,
+        Vec<usize>,
+        Vec<isize>,
+        Vec<f64>
+    );
 
-mod error {
-    use crate::schema::SchemaKind;
-    use std::ffi::OsString;
+    fn parse<T: CliValue>(long: &'static str, value: String) -> Result<T, CallError> {
+        T::from_str(&value).map_err(|err| CallError::ParseFailed {
+            long,
+            value,
+            message: err.to_string(),
+        })
+    }
+
+    impl<T: CliValue> SealedCliReturnValue for T {
+        fn convert(long: &'static str, raw: Box<dyn Any>) -> Result<Box<dyn Any>, CallError> {
+            let value = raw
+                .downcast::<String>()
+                .expect("value was not stored as string");
+            Ok(Box::new(parse::<T>(long, *value)?))
+        }
 
-    /// Invalid schema
-    #[derive(Debug, Clone, Eq, PartialEq)]
-    pub enum SchemaError {
-        /// The argument name was already provided for a different argument
-        NameAlreadyExists(String),
-        /// Currently not used
-        InvalidSchema(String),
-    }
-
-    /// Invalid arguments provided
-    #[derive(Debug, Clone, Eq, PartialEq)]
-    pub enum CallError {
-        ShortFlagNotFound(char),
-        LongFlagNotFound(String),
-        ExpectedValue(String, SchemaKind),
-        INan(String),
-        UNan(String),
-        NNan(String),
-        CombinedShortWithValue(String),
-        InvalidUtf8(OsString),
-        HelpPage,
+        fn check_default(long: &'static str, default: &str) -> Result<(), CallError> {
+            parse::<T>(long, default.to_string()).map(drop)
+        }
+    }
+
+    impl<T: CliValue> SealedCliReturnValue for Vec<T> {
+        fn convert(long: &'static str, raw: Box<dyn Any>) -> Result<Box<dyn Any>, CallError> {
+            let values = raw
+                .downcast::<Vec<String>>()
+                .expect("values were not stored as strings");
+            let values = values
+                .into_iter()
+                .map(|value| parse::<T>(long, value))
+                .collect::<Result<Vec<T>, _>>()?;
+            Ok(Box::new(values))
+        }
+
+        fn check_default(long: &'static str, default: &str) -> Result<(), CallError> {
+            parse::<T>(long, default.to_string()).map(drop)
+        }
     }
 }
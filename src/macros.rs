@@ -8,6 +8,11 @@
 /// arg!(LongOrShort: "long-or-short", 's' -> bool);
 /// arg!(OnlyLong: "only-long" -> bool);
 /// arg!(pub OtherModule: "other-module" -> bool);
+/// arg!(WithDefault: "with-default", 'd' -> usize = 4);
+/// arg!(
+///     /// The doc comment is used as the description in the help page
+///     WithDescription: "with-description" -> bool
+/// );
 /// ```
 ///
 ///
@@ -34,15 +39,40 @@
 ///     }
 /// }
 /// ```
+///
+/// The default value is written like it would be on the command line, so string defaults are
+/// string literals: `arg!(Color: "color" -> String = "auto")`.
+///
+/// After the type, options can be added:
+/// * `required`: parsing fails if the argument is not provided
+/// * `env = "VAR"`: the environment variable `VAR` is used if the argument is not provided
+///
+/// ```
+/// use badargs::arg;
+///
+/// arg!(Input: "input", 'i' -> String, required);
+/// arg!(Token: "token" -> String, env = "APP_TOKEN");
+/// arg!(Jobs: "jobs", 'j' -> usize = 1, env = "APP_JOBS");
+/// ```
+///
+/// Arguments with a default value or that are required implement [`AlwaysPresent`](crate::AlwaysPresent).
+/// An argument can't have both. Flags without a value, `bool` and [`Count`](crate::Count), can't
+/// have a default value either:
+/// ```compile_fail
+/// use badargs::arg;
+///
+/// arg!(Force: "force", 'f' -> bool = true);
+/// ```
 #[macro_export]
 macro_rules! arg {
-    ($vis:vis $name:ident: $long:literal, $short:literal -> $result:ty) => {
-        arg!(@$vis $name: ($long, ::std::option::Option::Some($short)) -> $result);
+    ($(#[doc = $doc:literal])* $vis:vis $name:ident: $long:literal, $short:literal -> $result:ty $(= $default:literal)? $(, $option:ident $(= $value:literal)?)*) => {
+        $crate::arg!(@[$($doc),*] $vis $name: ($long, ::std::option::Option::Some($short)) -> $result $(= $default)?; $($option $(= $value)?),*);
     };
-    ($vis:vis $name:ident: $long:literal -> $result:ty) => {
-        arg!(@$vis $name: ($long, ::std::option::Option::None) -> $result);
+    ($(#[doc = $doc:literal])* $vis:vis $name:ident: $long:literal -> $result:ty $(= $default:literal)? $(, $option:ident $(= $value:literal)?)*) => {
+        $crate::arg!(@[$($doc),*] $vis $name: ($long, ::std::option::Option::None) -> $result $(= $default)?; $($option $(= $value)?),*);
     };
-    (@$vis:vis $name:ident: ($long:literal, $short:expr) -> $result:ty) => {
+    (@[$($doc:literal),*] $vis:vis $name:ident: ($long:literal, $short:expr) -> $result:ty $(= $default:literal)?; $($option:ident $(= $value:literal)?),*) => {
+        $(#[doc = $doc])*
         #[derive(Default)]
         $vis struct $name;
 
@@ -56,6 +86,170 @@ macro_rules! arg {
             fn short() -> Option<char> {
                 $short
             }
+
+            $crate::__description!($($doc),*);
+
+            $(
+                fn default_value() -> Option<&'static str> {
+                    ::std::option::Option::Some(concat!($default))
+                }
+            )?
+
+            $(
+                $crate::arg!(@option $option $($value)?);
+            )*
+        }
+
+        $(
+            $crate::arg!(@check_default $result, $default);
+            $crate::arg!(@always_present $name $default);
+        )?
+        $(
+            $crate::arg!(@always_present $name $option);
+        )*
+    };
+    (@option required) => {
+        fn required() -> bool {
+            true
+        }
+    };
+    (@option env $var:literal) => {
+        fn env() -> Option<&'static str> {
+            ::std::option::Option::Some($var)
+        }
+    };
+    (@check_default $result:ty, $default:literal) => {
+        const _: () = ::std::assert!(
+            <$result as $crate::CliReturnValue>::KIND.takes_value(),
+            "flags without a value, like `bool` and `Count`, can't have a default value"
+        );
+    };
+    // the value from the environment might be missing
+    (@always_present $name:ident env) => {};
+    // the tokens after the name are the reason why it's always present
+    (@always_present $name:ident $($reason:tt)*) => {
+        impl $crate::AlwaysPresent for $name {}
+    };
+}
+
+/// Implements `description()` with the lines of the doc comments of the declaration
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __description {
+    ($($doc:literal),*) => {
+        fn description() -> Option<&'static str> {
+            let description = concat!($($doc, "\n"),*);
+            if description.is_empty() {
+                ::std::option::Option::None
+            } else {
+                ::std::option::Option::Some(description)
+            }
+        }
+    };
+}
+
+///
+/// Declare your positional arguments using this macro
+///
+/// By default, a positional argument takes exactly one operand. After the type, the arity can be changed:
+/// * `optional`: the operand can be left out
+/// * `many`: one or more operands are collected into a `Vec`
+/// ```
+/// use badargs::positional;
+/// use std::path::PathBuf;
+///
+/// positional!(
+///     /// The doc comment is used as the description in the help page
+///     Input: "input" -> PathBuf
+/// );
+/// positional!(Output: "output" -> PathBuf, optional);
+/// positional!(pub Files: "files" -> Vec<PathBuf>, many);
+/// ```
+#[macro_export]
+macro_rules! positional {
+    ($(#[doc = $doc:literal])* $vis:vis $name:ident: $positional:literal -> $result:ty $(, $arity:ident)?) => {
+        $(#[doc = $doc])*
+        #[derive(Default)]
+        $vis struct $name;
+
+        impl $crate::CliPositional for $name {
+            type Content = $result;
+
+            fn name() -> &'static str {
+                $positional
+            }
+
+            $(
+                fn arity() -> $crate::Arity {
+                    $crate::positional!(@arity $arity)
+                }
+            )?
+
+            $crate::__description!($($doc),*);
+        }
+    };
+    (@arity optional) => {
+        $crate::Arity::Optional
+    };
+    (@arity many) => {
+        $crate::Arity::OneOrMore
+    };
+}
+
+///
+/// Declare your subcommands using this macro
+///
+/// The arguments of the subcommand are written as a single argument, nested tuples of them or `()`.
+/// They can contain further [`Subcommands`](crate::Subcommands)
+/// ```
+/// use badargs::{arg, command, Subcommands};
+///
+/// arg!(Release: "release" -> bool);
+/// arg!(Name: "name" -> String);
+///
+/// command!(Build: "build" -> Release);
+/// command!(
+///     /// The doc comment is used as the description in the help page
+///     pub Clean: "clean" -> ()
+/// );
+/// command!(Add: "add" -> Name);
+/// command!(Remote: "remote" -> Subcommands<Add>);
+/// ```
+///
+/// ```
+/// use badargs::command;
+///
+/// command!(Clean: "clean" -> ());
+/// ```
+/// is a shorthand for
+/// ```
+/// use badargs::CliCommand;
+///
+/// struct Clean;
+///
+/// impl CliCommand for Clean {
+///     type Args = ();
+///
+///     fn name() -> &'static str {
+///         "clean"
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! command {
+    ($(#[doc = $doc:literal])* $vis:vis $name:ident: $command:literal -> $args:ty) => {
+        $(#[doc = $doc])*
+        #[derive(Default)]
+        $vis struct $name;
+
+        impl $crate::CliCommand for $name {
+            type Args = $args;
+
+            fn name() -> &'static str {
+                $command
+            }
+
+            $crate::__description!($($doc),*);
         }
     };
 }
@@ -88,6 +282,16 @@ macro_rules! arg {
 /// ```
 /// This only provides a minor benefit for programs with a small amount of args, but is
 /// very useful for larger arg amounts.
+///
+/// An [`App`](crate::App) can be passed before the arguments
+/// ```
+/// use badargs::arg;
+/// arg!(Force: "force", 'f' -> bool);
+///
+/// fn main() {
+///     let args = badargs::badargs!(app: badargs::App::new().version("1.0.0"); Force);
+/// }
+/// ```
 #[macro_export]
 macro_rules! badargs {
     (@inner $head:ty) => {
@@ -96,6 +300,14 @@ macro_rules! badargs {
     (@inner $head:ty, $($tail:ty),+) => {
         ($head, $crate::badargs!(@inner $($tail),+))
     };
+    (app: $app:expr; $($tail:ty),+) => {
+        {
+            #[allow(unused_parens)] // allow this because there might only be one arg
+            {
+                $app.badargs::<($crate::badargs!(@inner $($tail),+))>()
+            }
+        }
+    };
     ($($tail:ty),+) => {
         {
             #[allow(unused_parens)] // allow this because there might only be one arg
@@ -105,3 +317,84 @@ macro_rules! badargs {
         }
     };
 }
+
+///
+/// Creates an [`App`](crate::App) with the metadata from the `Cargo.toml` of the calling crate
+///
+/// This includes the name, version, description and authors
+/// ```
+/// let app = badargs::app!();
+/// ```
+#[macro_export]
+macro_rules! app {
+    () => {
+        $crate::App::new()
+            .name(env!("CARGO_PKG_NAME"))
+            .version(env!("CARGO_PKG_VERSION"))
+            .about(env!("CARGO_PKG_DESCRIPTION"))
+            .author(env!("CARGO_PKG_AUTHORS"))
+    };
+}
+
+///
+/// Declares an enum whose variants map to fixed strings, to be used as the value of an argument
+///
+/// Other values are rejected, and both the error message and the help page list the allowed
+/// values. The enum derives `Debug`, `Clone`, `Copy`, `PartialEq`, `Eq` and `Hash` and implements
+/// `FromStr`.
+/// ```
+/// use badargs::{arg, choice};
+///
+/// choice!(
+///     /// When to use colors
+///     pub enum Color {
+///         Auto = "auto",
+///         Always = "always",
+///         Never = "never",
+///     }
+/// );
+/// arg!(ColorArg: "color" -> Color = "auto");
+///
+/// let args = badargs::badargs_from::<ColorArg, _>(["ls", "--color", "never"]);
+/// assert_eq!(args.value::<ColorArg>(), &Color::Never);
+///
+/// let err = badargs::try_badargs_from::<ColorArg, _>(["ls", "--color", "blue"]).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "argument '--color' expected one of 'auto', 'always', 'never', but got 'blue'."
+/// );
+/// ```
+#[macro_export]
+macro_rules! choice {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($(#[$variant_meta:meta])* $variant:ident = $value:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        $vis enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )+
+        }
+
+        impl $crate::CliValue for $name {
+            const NAME: &'static str = stringify!($name);
+            const CHOICES: ::std::option::Option<&'static [&'static str]> =
+                ::std::option::Option::Some(&[$($value),+]);
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = ::std::string::String;
+
+            fn from_str(value: &str) -> ::std::result::Result<Self, Self::Err> {
+                match value {
+                    $($value => ::std::result::Result::Ok($name::$variant),)+
+                    _ => ::std::result::Result::Err(::std::format!(
+                        "invalid {}: '{}'",
+                        stringify!($name),
+                        value
+                    )),
+                }
+            }
+        }
+    };
+}
@@ -1,34 +1,255 @@
+use crate::app::App;
+use crate::config::Config;
 use crate::error::CallError;
-use crate::schema::{Schema, SchemaKind};
+use crate::schema::{Schema, SchemaCommand, SchemaKind};
+use crate::suggestions;
+use crate::{Arity, Count, ValueSource};
 use std::any::Any;
 use std::collections::HashMap;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::str::FromStr;
 
 type Result<T> = std::result::Result<T, CallError>;
 
 #[derive(Debug, Default)]
-pub(crate) struct CliArgs {
+pub struct CliArgs {
     args: HashMap<&'static str, Box<dyn Any>>,
+    /// The unnamed arguments, with invalid UTF-8 replaced
     unnamed: Vec<String>,
+    /// The unnamed arguments as they were passed
+    unnamed_os: Vec<OsString>,
+    /// The index into `unnamed` and `unnamed_os` where the arguments after `--` start
+    terminator: Option<usize>,
+    /// The name and arguments of the subcommand that was chosen
+    subcommand: Option<(&'static str, Box<CliArgs>)>,
+    /// Where the values of the arguments came from
+    sources: HashMap<&'static str, ValueSource>,
+    /// The config file that some of the values came from
+    config: Option<PathBuf>,
+}
+
+/// A command that was entered, either the program itself or a subcommand
+///
+/// The arguments of outer commands are global, so they can also be given after a subcommand
+struct Level<'a> {
+    /// The name of the subcommand, `None` for the program itself
+    name: Option<&'static str>,
+    schema: &'a Schema,
+    args: CliArgs,
 }
 
 impl CliArgs {
-    pub fn from_args(schema: &Schema, mut args: impl Iterator<Item = OsString>) -> Result<Self> {
-        let mut result = Self::default();
+    /// Parses the arguments
+    ///
+    /// On failure, the names of the subcommands that were entered are returned with the error
+    pub fn from_args(
+        schema: &Schema,
+        app: &App,
+        args: impl Iterator<Item = OsString>,
+    ) -> std::result::Result<Self, (CallError, Vec<&'static str>)> {
+        let mut levels = vec![Level {
+            name: None,
+            schema,
+            args: Self::default(),
+        }];
+        if let Err(err) = parse_tokens(&mut levels, app, args) {
+            return Err((err, subcommand_path(&levels)));
+        }
 
-        while let Some(arg) = args.next() {
-            let arg = arg.into_string().map_err(CallError::InvalidUtf8)?;
+        // the config is only read after the tokens, so `--help` still works with a broken config
+        let config = match &app.config_file {
+            Some(path) => Config::load(path).map_err(|err| (err, Vec::new()))?,
+            None => None,
+        };
+        if let Some(config) = &config {
+            config.check(schema).map_err(|err| (err, Vec::new()))?;
+        }
 
-            if let Some(long) = arg.strip_prefix("--") {
-                parse_long(schema, &mut result, long, &mut args)?;
-            } else if let Some(shorts) = arg.strip_prefix('-') {
-                parse_shorts(schema, &mut result, shorts, &mut args)?;
-            } else {
-                result.unnamed.push(arg);
+        // the subcommands are finished first, so their arguments can be nested into the outer ones
+        let mut subcommand = None;
+        while let Some(mut level) = levels.pop() {
+            let mut section = subcommand_path(&levels);
+            section.extend(level.name);
+            if let Err(err) = level.args.finish(level.schema, &section, config.as_ref()) {
+                return Err((err, section));
+            }
+            level.args.subcommand = subcommand.take();
+            match level.name {
+                Some(name) => subcommand = Some((name, Box::new(level.args))),
+                None => return Ok(level.args),
             }
         }
 
-        Ok(result)
+        unreachable!("there is always the outermost level")
+    }
+
+    /// Assign the operands to the positional arguments, insert the values from the environment, the
+    /// config file and the default values and check that the required arguments are present
+    ///
+    /// `section` are the names of the subcommands that lead to this command
+    fn finish(&mut self, schema: &Schema, section: &[&str], config: Option<&Config>) -> Result<()> {
+        let missing_positionals = self.assign_positionals(schema)?;
+
+        // everything up to here came from the command line
+        for &long in self.args.keys() {
+            self.sources.insert(long, ValueSource::CommandLine);
+        }
+
+        for command in schema.arguments() {
+            if self.args.contains_key(command.long) {
+                continue;
+            }
+            if let Some(var) = command.env {
+                if let Some(value) = std::env::var_os(var) {
+                    self.insert_env(command, var, value)?;
+                    continue;
+                }
+            }
+            if let Some(config) = config {
+                if config.entries(section, command.long).next().is_some() {
+                    self.insert_config(command, section, config)?;
+                    continue;
+                }
+            }
+            if let Some(default) = command.default {
+                insert_value(command, self, default)?;
+                self.sources.insert(command.long, ValueSource::Default);
+            }
+        }
+
+        let missing = schema
+            .arguments()
+            .filter(|command| command.required && !self.args.contains_key(command.long))
+            .map(|command| command.long)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() || !missing_positionals.is_empty() {
+            return Err(CallError::MissingRequired {
+                longs: missing,
+                positionals: missing_positionals,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Insert the value of the environment variable `var` for an argument
+    fn insert_env(
+        &mut self,
+        command: &SchemaCommand,
+        var: &'static str,
+        value: OsString,
+    ) -> Result<()> {
+        self.insert_setting(command, value)
+            .map_err(|err| CallError::FromEnv {
+                var,
+                err: Box::new(err),
+            })?;
+
+        // flags that are turned off by the variable have no value, so they have no source either
+        if self.args.contains_key(command.long) {
+            self.sources
+                .insert(command.long, ValueSource::Environment(var));
+        }
+        Ok(())
+    }
+
+    /// Insert the values from the config file for an argument
+    ///
+    /// Arguments that can be repeated get all values, other arguments the last one
+    fn insert_config(
+        &mut self,
+        command: &SchemaCommand,
+        section: &[&str],
+        config: &Config,
+    ) -> Result<()> {
+        for entry in config.entries(section, command.long) {
+            self.insert_setting(command, entry.value.clone().into())
+                .map_err(|err| CallError::FromConfig {
+                    path: config.path().to_path_buf(),
+                    line: entry.line,
+                    err: Box::new(err),
+                })?;
+            if self.args.contains_key(command.long) {
+                self.sources
+                    .insert(command.long, ValueSource::ConfigFile { line: entry.line });
+            }
+        }
+        self.config = Some(config.path().to_path_buf());
+        Ok(())
+    }
+
+    /// Insert a value for an argument that came from the environment or the config file
+    fn insert_setting(&mut self, command: &SchemaCommand, value: OsString) -> Result<()> {
+        let long = command.long;
+        match command.kind {
+            // flags are set by `1` or `true`, and unset by `0`, `false` or an empty value
+            SchemaKind::Bool | SchemaKind::Count => match value.to_str() {
+                Some("1") | Some("true") => {
+                    parse_value(command.kind, self, long, None, &mut std::iter::empty())
+                }
+                Some("0") | Some("false") | Some("") => Ok(()),
+                _ => Err(CallError::InvalidValue {
+                    long,
+                    value: value.to_string_lossy().into_owned(),
+                    kind: command.kind,
+                }),
+            },
+            kind => parse_value(kind, self, long, None, &mut std::iter::once(value)),
+        }
+    }
+
+    /// Assign the operands to the positional arguments in order
+    ///
+    /// Optional positional arguments only get an operand if the following ones still get enough of them.
+    /// Without positional arguments in the schema, the operands are only available as unnamed arguments
+    ///
+    /// Returns the names of the positional arguments that did not get enough operands
+    fn assign_positionals(&mut self, schema: &Schema) -> Result<Vec<&'static str>> {
+        let positionals = schema.positionals();
+        if positionals.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut operands = self.unnamed_os.clone().into_iter();
+        let mut remaining = operands.len();
+        let mut missing = Vec::new();
+
+        for (idx, positional) in positionals.iter().enumerate() {
+            // the operands that the following positional arguments need at least
+            let needed = positionals[idx + 1..]
+                .iter()
+                .filter(|positional| positional.arity != Arity::Optional)
+                .count();
+            let wanted = match positional.arity {
+                Arity::One => 1,
+                Arity::Optional => usize::from(remaining > needed),
+                Arity::OneOrMore => remaining.saturating_sub(needed).max(1),
+            };
+            let taken = wanted.min(remaining);
+            if taken == 0 && positional.arity != Arity::Optional {
+                missing.push(positional.name);
+            }
+
+            for operand in operands.by_ref().take(taken) {
+                let mut operand = std::iter::once(operand);
+                parse_value(positional.kind, self, positional.name, None, &mut operand)
+                    .map_err(|err| err.for_positional(positional.name))?;
+            }
+            remaining -= taken;
+        }
+
+        match operands.next() {
+            Some(extra) => Err(CallError::UnexpectedPositional {
+                value: extra.to_string_lossy().into_owned(),
+            }),
+            None => Ok(missing),
+        }
+    }
+
+    /// Where the value of an argument came from
+    pub fn source(&self, long: &str) -> Option<ValueSource> {
+        self.sources.get(long).copied()
     }
 
     /// Get a value from the map, expecting it to have type T
@@ -41,125 +262,517 @@ impl CliArgs {
         &self.unnamed
     }
 
+    pub fn unnamed_os(&self) -> &[OsString] {
+        &self.unnamed_os
+    }
+
+    pub fn leading(&self) -> &[String] {
+        let end = self.terminator.unwrap_or(self.unnamed.len());
+        &self.unnamed[..end]
+    }
+
+    pub fn trailing(&self) -> Option<&[String]> {
+        self.terminator.map(|start| &self.unnamed[start..])
+    }
+
+    pub fn leading_os(&self) -> &[OsString] {
+        let end = self.terminator.unwrap_or(self.unnamed_os.len());
+        &self.unnamed_os[..end]
+    }
+
+    pub fn trailing_os(&self) -> Option<&[OsString]> {
+        self.terminator.map(|start| &self.unnamed_os[start..])
+    }
+
+    /// The names of the subcommands that were chosen, from the outermost to the innermost
+    pub fn subcommand_path(&self) -> Vec<&'static str> {
+        let mut path = Vec::new();
+        let mut args = self;
+        while let Some((name, subcommand)) = &args.subcommand {
+            path.push(*name);
+            args = subcommand;
+        }
+        path
+    }
+
+    /// Take out the name and arguments of the subcommand that was chosen
+    pub fn take_subcommand(&mut self) -> Option<(&'static str, CliArgs)> {
+        self.subcommand.take().map(|(name, args)| (name, *args))
+    }
+
+    pub fn subcommand_mut(&mut self) -> Option<(&'static str, &mut CliArgs)> {
+        self.subcommand
+            .as_mut()
+            .map(|(name, args)| (*name, &mut **args))
+    }
+
+    fn push_unnamed(&mut self, arg: OsString) {
+        self.unnamed.push(arg.to_string_lossy().into_owned());
+        self.unnamed_os.push(arg);
+    }
+
     fn insert(&mut self, long: &'static str, value: Box<dyn Any>) {
         self.args.insert(long, value);
     }
+
+    /// Replace the stored value of an argument with the converted value
+    pub fn convert(
+        &mut self,
+        long: &'static str,
+        convert: impl FnOnce(&'static str, Box<dyn Any>) -> Result<Box<dyn Any>>,
+    ) -> Result<()> {
+        if let Some(raw) = self.args.remove(long) {
+            let value =
+                convert(long, raw).map_err(|err| match (self.sources.get(long), &self.config) {
+                    (Some(&ValueSource::Environment(var)), _) => CallError::FromEnv {
+                        var,
+                        err: Box::new(err),
+                    },
+                    (Some(&ValueSource::ConfigFile { line }), Some(path)) => {
+                        CallError::FromConfig {
+                            path: path.clone(),
+                            line,
+                            err: Box::new(err),
+                        }
+                    }
+                    _ => err,
+                })?;
+            self.args.insert(long, value);
+        }
+        Ok(())
+    }
+
+    /// Count one more occurrence of an argument
+    fn increment(&mut self, long: &'static str) {
+        self.args
+            .entry(long)
+            .or_insert_with(|| Box::new(Count(0)))
+            .downcast_mut::<Count>()
+            .expect("argument was inserted with a different type")
+            .0 += 1;
+    }
+
+    /// Append a value to the list of values for an argument that can be repeated
+    fn push<T: Any>(&mut self, long: &'static str, value: T) {
+        self.args
+            .entry(long)
+            .or_insert_with(|| Box::new(Vec::<T>::new()))
+            .downcast_mut::<Vec<T>>()
+            .expect("argument was inserted with a different type")
+            .push(value);
+    }
+}
+
+fn current<'l, 'a>(levels: &'l mut [Level<'a>]) -> &'l mut Level<'a> {
+    levels
+        .last_mut()
+        .expect("there is always the outermost level")
+}
+
+/// Sorts the tokens into the commands they belong to
+fn parse_tokens(
+    levels: &mut Vec<Level<'_>>,
+    app: &App,
+    mut args: impl Iterator<Item = OsString>,
+) -> Result<()> {
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            // everything after `--` is unnamed, even if it looks like a flag
+            let current = &mut current(levels).args;
+            current.terminator = Some(current.unnamed.len());
+            for arg in args {
+                current.push_unnamed(arg);
+            }
+            break;
+        }
+
+        // only flags have to be valid UTF-8, values and unnamed arguments are kept as they are
+        let bytes = arg.as_encoded_bytes();
+        if bytes.starts_with(b"--") {
+            parse_long(levels, app, &arg, &mut args)?;
+        } else if bytes.starts_with(b"-") {
+            parse_shorts(levels, app, &arg, &mut args)?;
+        } else {
+            parse_unnamed(levels, arg)?;
+        }
+    }
+    Ok(())
+}
+
+/// The names of the subcommands that were entered
+fn subcommand_path(levels: &[Level<'_>]) -> Vec<&'static str> {
+    levels.iter().filter_map(|level| level.name).collect()
+}
+
+/// Find the innermost command that has this long flag, with its index into `levels`
+fn find_long(levels: &[Level<'_>], long: &str) -> Option<(usize, SchemaCommand)> {
+    levels
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(idx, level)| Some((idx, *level.schema.long(long)?)))
+}
+
+/// Find the innermost command that has this short flag, with its index into `levels`
+fn find_short(levels: &[Level<'_>], short: char) -> Option<(usize, SchemaCommand)> {
+    levels
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(idx, level)| Some((idx, *level.schema.short(short)?)))
+}
+
+/// An argument that is not a flag is either a subcommand, if the current command has them,
+/// or an unnamed argument
+fn parse_unnamed(levels: &mut Vec<Level<'_>>, arg: OsString) -> Result<()> {
+    let schema = current(levels).schema;
+    if schema.subcommands().is_empty() {
+        current(levels).args.push_unnamed(arg);
+        return Ok(());
+    }
+
+    let name = arg.to_string_lossy();
+    let subcommand = schema
+        .subcommand(&name)
+        .ok_or_else(|| CallError::SubcommandNotFound {
+            name: name.to_string(),
+            suggestions: suggestions::similar(
+                &name,
+                schema
+                    .subcommands()
+                    .iter()
+                    .map(|subcommand| subcommand.name),
+            )
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+        })?;
+
+    levels.push(Level {
+        name: Some(subcommand.name),
+        schema: &subcommand.schema,
+        args: CliArgs::default(),
+    });
+    Ok(())
+}
+
+/// The rest of a token, starting after the first `start` bytes
+///
+/// The bytes before `start` have to be valid UTF-8
+fn os_suffix(token: &OsStr, start: usize) -> &OsStr {
+    // SAFETY: `start` is right after a valid UTF-8 prefix, `from_encoded_bytes_unchecked` allows
+    // splitting there
+    unsafe { OsStr::from_encoded_bytes_unchecked(&token.as_encoded_bytes()[start..]) }
 }
 
 fn parse_shorts(
-    schema: &Schema,
-    results: &mut CliArgs,
-    shorts: &str,
+    levels: &mut [Level<'_>],
+    app: &App,
+    token: &OsStr,
     args: &mut impl Iterator<Item = OsString>,
 ) -> Result<()> {
     // there are kinds of short arguments
-    // single shorts that takes values: `-o main`
+    // single shorts that takes values: `-o main` or `-omain`
     // multiple flags combined: `-xzf`
-    // combining these is invalid: `-xo main`
-
-    let mut all_shorts = shorts.chars();
+    // flags combined with a value taking short at the end: `-xzf archive.tar` or `-xzfarchive.tar`
 
-    let first_flag = all_shorts.next();
-
-    if let Some(flag) = first_flag {
-        let command = schema
-            .short(flag)
-            .ok_or(CallError::ShortFlagNotFound(flag))?;
-
-        parse_value(command.kind, results, command.long, args)?;
-    } else {
+    let bytes = &token.as_encoded_bytes()[1..];
+    if bytes.is_empty() {
         // '-' is a valid argument, like the `cat -`
-        results.unnamed.push("-".to_string());
+        current(levels).args.push_unnamed("-".into());
+        return Ok(());
     }
 
-    for flag in all_shorts {
-        let command = schema
-            .short(flag)
-            .ok_or(CallError::ShortFlagNotFound(flag))?;
+    // the flags have to be valid UTF-8, only a value at the end can be anything
+    let shorts = match std::str::from_utf8(bytes) {
+        Ok(shorts) => shorts,
+        Err(err) => std::str::from_utf8(&bytes[..err.valid_up_to()])
+            .expect("the bytes up to here are valid"),
+    };
+
+    for (idx, flag) in shorts.char_indices() {
+        if find_short(levels, flag).is_none() {
+            if Some(flag) == app.help_short {
+                return Err(CallError::HelpPage(subcommand_path(levels)));
+            }
+            if flag == 'V' && app.version.is_some() {
+                return Err(CallError::Version);
+            }
+        }
+        let (level, command) =
+            find_short(levels, flag).ok_or_else(|| CallError::ShortFlagNotFound {
+                flag,
+                token: token.to_string_lossy().into_owned(),
+                suggestions: suggest_short(levels, flag, shorts),
+            })?;
+        let results = &mut levels[level].args;
 
-        if let SchemaKind::Bool = command.kind {
-            results.insert(command.long, Box::new(true));
+        if !command.kind.takes_value() {
+            parse_value(command.kind, results, command.long, None, args)?;
         } else {
-            return Err(CallError::CombinedShortWithValue(command.long.to_string()));
+            // the rest of the token is the value, if there is anything left
+            let rest = os_suffix(token, 1 + idx + flag.len_utf8());
+            let value = if rest.is_empty() { None } else { Some(rest) };
+            return parse_value(command.kind, results, command.long, value, args);
         }
     }
 
+    if shorts.len() < bytes.len() {
+        return Err(CallError::InvalidUtf8(token.to_owned()));
+    }
     Ok(())
 }
 
 fn parse_long(
-    schema: &Schema,
-    results: &mut CliArgs,
-    long: &str,
+    levels: &mut [Level<'_>],
+    app: &App,
+    token: &OsStr,
     args: &mut impl Iterator<Item = OsString>,
 ) -> Result<()> {
-    if long == "help" {
-        return Err(CallError::HelpPage);
+    let bytes = token.as_encoded_bytes();
+    // `--output=main.c` passes the value in the same token
+    let end = bytes
+        .iter()
+        .position(|&byte| byte == b'=')
+        .unwrap_or(bytes.len());
+    let long = std::str::from_utf8(&bytes[2..end])
+        .map_err(|_| CallError::InvalidUtf8(token.to_owned()))?;
+    let value = if end < bytes.len() {
+        Some(os_suffix(token, end + 1))
+    } else {
+        None
+    };
+
+    if find_long(levels, long).is_none() {
+        if Some(long) == app.help_long {
+            return Err(CallError::HelpPage(subcommand_path(levels)));
+        }
+        if long == "version" && app.version.is_some() {
+            return Err(CallError::Version);
+        }
+    }
+    let (level, command) = find_long(levels, long).ok_or_else(|| CallError::LongFlagNotFound {
+        flag: long.to_string(),
+        token: token.to_string_lossy().into_owned(),
+        suggestions: suggest_long(levels, long),
+    })?;
+
+    parse_value(
+        command.kind,
+        &mut levels[level].args,
+        command.long,
+        value,
+        args,
+    )
+}
+
+fn suggest_long(levels: &[Level<'_>], long: &str) -> Vec<String> {
+    let longs = levels
+        .iter()
+        .rev()
+        .flat_map(|level| level.schema.arguments())
+        .map(|command| command.long);
+    suggestions::similar(long, longs)
+        .into_iter()
+        .map(|long| format!("--{}", long))
+        .collect()
+}
+
+fn suggest_short(levels: &[Level<'_>], flag: char, shorts: &str) -> Vec<String> {
+    // `-output` was probably meant to be `--output`
+    if shorts.chars().count() > 2 {
+        let longs = suggest_long(levels, shorts);
+        if !longs.is_empty() {
+            return longs;
+        }
     }
-    let command = schema
-        .long(long)
-        .ok_or_else(|| CallError::LongFlagNotFound(long.to_string()))?;
 
-    parse_value(command.kind, results, command.long, args)
+    // `-F` instead of `-f`
+    flag.to_lowercase()
+        .chain(flag.to_uppercase())
+        .filter(|&other| other != flag && find_short(levels, other).is_some())
+        .map(|other| format!("-{}", other))
+        .collect()
+}
+
+/// Insert a value for an argument that did not come from the command line
+fn insert_value(command: &SchemaCommand, results: &mut CliArgs, value: &str) -> Result<()> {
+    parse_value(
+        command.kind,
+        results,
+        command.long,
+        Some(OsStr::new(value)),
+        &mut std::iter::empty(),
+    )
+}
+
+/// Check whether a value from the config file would be valid for an argument
+pub fn check_setting(command: &SchemaCommand, value: &str) -> Result<()> {
+    CliArgs::default().insert_setting(command, value.into())
+}
+
+/// Check whether a value would be valid for an argument
+pub fn check_value(command: &SchemaCommand, value: &str) -> Result<()> {
+    insert_value(command, &mut CliArgs::default(), value)
+}
+
+/// Get the value for an argument, either from the same token or from the next one
+fn take_value(
+    kind: SchemaKind,
+    long: &'static str,
+    value: Option<&OsStr>,
+    args: &mut impl Iterator<Item = OsString>,
+) -> Result<String> {
+    take_os_value(kind, long, value, args)?
+        .into_string()
+        .map_err(CallError::InvalidUtf8)
+}
+
+/// Like [`take_value`], but the value does not have to be valid UTF-8
+fn take_os_value(
+    kind: SchemaKind,
+    long: &'static str,
+    value: Option<&OsStr>,
+    args: &mut impl Iterator<Item = OsString>,
+) -> Result<OsString> {
+    match value {
+        Some(value) => Ok(value.to_owned()),
+        None => args.next().ok_or(CallError::ExpectedValue { long, kind }),
+    }
 }
 
 fn parse_value(
     kind: SchemaKind,
     results: &mut CliArgs,
     long: &'static str,
+    value: Option<&OsStr>,
     args: &mut impl Iterator<Item = OsString>,
 ) -> Result<()> {
     match kind {
         SchemaKind::String => {
-            let string = args
-                .next()
-                .ok_or_else(|| CallError::ExpectedValue(long.to_string(), kind))?
-                .into_string()
-                .map_err(CallError::InvalidUtf8)?;
+            let string = take_value(kind, long, value, args)?;
             results.insert(long, Box::new(string));
         }
+        SchemaKind::OsString => {
+            let string = take_os_value(kind, long, value, args)?;
+            results.insert(long, Box::new(string));
+        }
+        SchemaKind::Path => {
+            let path = take_os_value(kind, long, value, args)?;
+            results.insert(long, Box::new(PathBuf::from(path)));
+        }
         SchemaKind::IInt => {
-            let integer = args
-                .next()
-                .ok_or_else(|| CallError::ExpectedValue(long.to_string(), kind))?
-                .into_string()
-                .map_err(CallError::InvalidUtf8)?
-                .parse::<isize>()
-                .map_err(|_| CallError::INan(long.to_string()))?;
+            let value = take_value(kind, long, value, args)?;
+            let integer = parse_from_str::<isize>(kind, long, value)?;
             results.insert(long, Box::new(integer))
         }
         SchemaKind::UInt => {
-            let integer = args
-                .next()
-                .ok_or_else(|| CallError::ExpectedValue(long.to_string(), kind))?
-                .into_string()
-                .map_err(CallError::InvalidUtf8)?
-                .parse::<usize>()
-                .map_err(|_| CallError::UNan(long.to_string()))?;
+            let value = take_value(kind, long, value, args)?;
+            let integer = parse_from_str::<usize>(kind, long, value)?;
             results.insert(long, Box::new(integer))
         }
         SchemaKind::Num => {
-            let float = args
-                .next()
-                .ok_or_else(|| CallError::ExpectedValue(long.to_string(), kind))?
-                .into_string()
-                .map_err(CallError::InvalidUtf8)?
-                .parse::<f64>()
-                .map_err(|_| CallError::NNan(long.to_string()))?;
+            let value = take_value(kind, long, value, args)?;
+            let float = parse_from_str::<f64>(kind, long, value)?;
             results.insert(long, Box::new(float))
         }
         SchemaKind::Bool => {
+            if let Some(value) = value {
+                return Err(CallError::UnexpectedValue {
+                    long,
+                    value: value.to_string_lossy().into_owned(),
+                });
+            }
             results.insert(long, Box::new(true));
         }
+        SchemaKind::Count => {
+            if let Some(value) = value {
+                return Err(CallError::UnexpectedValue {
+                    long,
+                    value: value.to_string_lossy().into_owned(),
+                });
+            }
+            results.increment(long);
+        }
+        SchemaKind::Custom { .. } => {
+            let value = take_value(kind, long, value, args)?;
+            check_choice(kind, long, value.clone())?;
+            results.insert(long, Box::new(value));
+        }
+        SchemaKind::Vec(SchemaKind::OsString) => {
+            results.push(long, take_os_value(kind, long, value, args)?)
+        }
+        SchemaKind::Vec(SchemaKind::Path) => {
+            let path = take_os_value(kind, long, value, args)?;
+            results.push(long, PathBuf::from(path))
+        }
+        SchemaKind::Vec(inner) => {
+            let value = take_value(kind, long, value, args)?;
+            match inner {
+                SchemaKind::Custom { .. } => {
+                    check_choice(*inner, long, value.clone())?;
+                    results.push(long, value)
+                }
+                SchemaKind::String => results.push(long, value),
+                SchemaKind::IInt => results.push(long, parse_from_str::<isize>(kind, long, value)?),
+                SchemaKind::UInt => results.push(long, parse_from_str::<usize>(kind, long, value)?),
+                SchemaKind::Num => results.push(long, parse_from_str::<f64>(kind, long, value)?),
+                SchemaKind::Bool
+                | SchemaKind::Count
+                | SchemaKind::Vec(_)
+                | SchemaKind::OsString
+                | SchemaKind::Path => {
+                    unreachable!("no CliReturnValue has this kind")
+                }
+            }
+        }
     };
     Ok(())
 }
 
+/// Checks that the value is one of the allowed choices, if there are any
+fn check_choice(kind: SchemaKind, long: &'static str, value: String) -> Result<()> {
+    match kind {
+        SchemaKind::Custom {
+            choices: Some(choices),
+            ..
+        } if !choices.contains(&value.as_str()) => {
+            Err(CallError::InvalidValue { long, value, kind })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn parse_from_str<T: FromStr>(kind: SchemaKind, long: &'static str, value: String) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| CallError::InvalidValue { long, value, kind })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::arg;
-    use crate::schema::Schema;
+    use crate::schema::{IntoSchema, Schema};
+
+    fn from_args(
+        schema: &Schema,
+        app: &App,
+        args: impl Iterator<Item = OsString>,
+    ) -> Result<CliArgs> {
+        CliArgs::from_args(schema, app, args).map_err(|(err, _)| err)
+    }
+
+    fn tokens(args: &str) -> impl Iterator<Item = OsString> + '_ {
+        args.split_whitespace().map(OsString::from)
+    }
+
+    /// Parses the whitespace separated `args` with the schema `S` and converts the values
+    fn parse_with<S: IntoSchema>(app: &App, args: &str) -> Result<CliArgs> {
+        let mut args = from_args(&Schema::create::<S>().unwrap(), app, tokens(args))?;
+        S::convert(&mut args)?;
+        Ok(args)
+    }
 
     arg!(OutFile: "output", 'o' -> String);
     arg!(Input: "input", 'i' -> String);
@@ -171,20 +784,26 @@ mod test {
     arg!(OLevel: "olevel", 'l' -> usize);
     arg!(Iq: "iq", 'q' -> isize);
 
-    fn schema() -> Schema {
-        Schema::create::<(
-            (OutFile, (Input, (OLevel, Iq))),
-            ((Force, Gentle), SetUpstream),
-        )>()
-        .unwrap()
-    }
+    arg!(Include: "include", 'I' -> Vec<String>);
+    arg!(Jobs: "jobs" -> Vec<usize>);
+    arg!(Verbose: "verbose", 'v' -> Count);
+    arg!(Threads: "threads", 't' -> usize = 4);
+
+    arg!(Source: "source", 's' -> String, required);
+    arg!(Target: "target" -> String, required);
+
+    arg!(Host: "host", 'h' -> String);
+
+    type AllArgs = (
+        (OutFile, (Input, (OLevel, Iq))),
+        (
+            (Force, Gentle),
+            (SetUpstream, (Include, (Jobs, (Verbose, Threads)))),
+        ),
+    );
 
     fn parse_args(args: &str) -> Result<CliArgs> {
-        CliArgs::from_args(
-            &schema(),
-            args.split_whitespace()
-                .map(|s| OsString::from(s.to_owned())),
-        )
+        parse_with::<AllArgs>(&App::default(), args)
     }
 
     #[test]
@@ -226,6 +845,28 @@ mod test {
         assert_eq!(args.get::<bool>("force"), Some(&true));
     }
 
+    #[test]
+    fn attached_short_values() {
+        let args = parse_args("-omain.c -l2 -q-5").unwrap();
+        assert_eq!(args.get::<String>("output"), Some(&"main.c".to_string()));
+        assert_eq!(args.get::<usize>("olevel"), Some(&2));
+        assert_eq!(args.get::<isize>("iq"), Some(&-5));
+    }
+
+    #[test]
+    fn combined_shorts_with_value() {
+        let args = parse_args("-gfomain.c").unwrap();
+        assert_eq!(args.get::<bool>("gentle"), Some(&true));
+        assert_eq!(args.get::<bool>("force"), Some(&true));
+        assert_eq!(args.get::<String>("output"), Some(&"main.c".to_string()));
+
+        let args = parse_args("-gfo main.c").unwrap();
+        assert_eq!(args.get::<bool>("gentle"), Some(&true));
+        assert_eq!(args.get::<bool>("force"), Some(&true));
+        assert_eq!(args.get::<String>("output"), Some(&"main.c".to_string()));
+        assert_eq!(args.unnamed(), &[] as &[String]);
+    }
+
     #[test]
     fn long_flags() {
         let args = parse_args("--force --gentle").unwrap();
@@ -251,4 +892,864 @@ mod test {
         assert_eq!(args.get::<usize>("olevel"), None);
         assert_eq!(args.get::<String>("input"), None)
     }
+
+    #[test]
+    fn terminator() {
+        let args = parse_args("-f hallo -- --gentle -o -").unwrap();
+        assert_eq!(args.get::<bool>("force"), Some(&true));
+        assert_eq!(args.get::<bool>("gentle"), None);
+        assert_eq!(args.unnamed(), &["hallo", "--gentle", "-o", "-"]);
+        assert_eq!(args.leading(), &["hallo"]);
+        assert_eq!(args.trailing().unwrap(), &["--gentle", "-o", "-"]);
+    }
+
+    #[test]
+    fn terminator_twice() {
+        let args = parse_args("-- -- welt").unwrap();
+        assert_eq!(args.unnamed(), &["--", "welt"]);
+        assert_eq!(args.leading(), &[] as &[String]);
+    }
+
+    #[test]
+    fn no_terminator() {
+        let args = parse_args("hallo welt").unwrap();
+        assert_eq!(args.leading(), &["hallo", "welt"]);
+        assert_eq!(args.trailing(), None);
+    }
+
+    #[test]
+    fn long_params_with_equals() {
+        let args = parse_args("--output=main.c --iq=-75 --set-upstream=origin=main").unwrap();
+        assert_eq!(args.get::<isize>("iq"), Some(&-75));
+        assert_eq!(args.get::<String>("output"), Some(&"main.c".to_string()));
+        assert_eq!(
+            args.get::<String>("set-upstream"),
+            Some(&"origin=main".to_string())
+        );
+    }
+
+    #[test]
+    fn long_empty_value_with_equals() {
+        let args = parse_args("--output=").unwrap();
+        assert_eq!(args.get::<String>("output"), Some(&"".to_string()));
+    }
+
+    #[test]
+    fn long_flag_with_equals() {
+        let args = parse_args("--force=yes");
+        assert_eq!(
+            args.unwrap_err(),
+            CallError::UnexpectedValue {
+                long: "force",
+                value: "yes".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_flags() {
+        let err = parse_args("-gx").unwrap_err();
+        assert_eq!(err.flag(), Some("-x".to_string()));
+        assert_eq!(err.token(), Some("-gx".as_ref()));
+
+        let err = parse_args("--forc=yes").unwrap_err();
+        assert_eq!(err.flag(), Some("--forc".to_string()));
+        assert_eq!(err.token(), Some("--forc=yes".as_ref()));
+    }
+
+    #[test]
+    fn invalid_value() {
+        let err = parse_args("-l -5").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::InvalidValue {
+                long: "olevel",
+                value: "-5".to_string(),
+                kind: SchemaKind::UInt,
+            }
+        );
+        assert_eq!(err.flag(), Some("--olevel".to_string()));
+        assert_eq!(err.token(), Some("-5".as_ref()));
+    }
+
+    #[test]
+    fn missing_value() {
+        let err = parse_args("-f --output").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::ExpectedValue {
+                long: "output",
+                kind: SchemaKind::String,
+            }
+        );
+    }
+
+    #[test]
+    fn repeated_values() {
+        let args = parse_args("-I inc --include=vendor -Isrc --jobs 1 --jobs=4").unwrap();
+        assert_eq!(
+            args.get::<Vec<String>>("include"),
+            Some(&vec![
+                "inc".to_string(),
+                "vendor".to_string(),
+                "src".to_string()
+            ])
+        );
+        assert_eq!(args.get::<Vec<usize>>("jobs"), Some(&vec![1, 4]));
+    }
+
+    #[test]
+    fn repeated_values_absent() {
+        let args = parse_args("-f").unwrap();
+        assert_eq!(args.get::<Vec<String>>("include"), None);
+    }
+
+    #[test]
+    fn repeated_invalid_value() {
+        let err = parse_args("--jobs 1 --jobs many").unwrap_err();
+        assert_eq!(err.token(), Some("many".as_ref()));
+    }
+
+    #[test]
+    fn count_flags() {
+        let args = parse_args("-vvv").unwrap();
+        assert_eq!(args.get::<Count>("verbose"), Some(&Count(3)));
+
+        let args = parse_args("-v -fv --verbose --verbose").unwrap();
+        assert_eq!(args.get::<Count>("verbose"), Some(&Count(4)));
+        assert_eq!(args.get::<bool>("force"), Some(&true));
+
+        let args = parse_args("-f").unwrap();
+        assert_eq!(args.get::<Count>("verbose"), None);
+    }
+
+    #[test]
+    fn count_with_value() {
+        let err = parse_args("--verbose=3").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::UnexpectedValue {
+                long: "verbose",
+                value: "3".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn value_sources() {
+        let args = parse_args("-v -o main").unwrap();
+        assert_eq!(args.source("output"), Some(ValueSource::CommandLine));
+        assert_eq!(args.source("verbose"), Some(ValueSource::CommandLine));
+        assert_eq!(args.source("threads"), Some(ValueSource::Default));
+        assert_eq!(args.source("force"), None);
+
+        let args = parse_args("--threads 4").unwrap();
+        assert_eq!(args.source("threads"), Some(ValueSource::CommandLine));
+
+        let args = parse_positionals("a b").unwrap();
+        assert_eq!(args.source("dest"), Some(ValueSource::CommandLine));
+        assert_eq!(args.source("mode"), None);
+    }
+
+    #[test]
+    fn default_values() {
+        let args = parse_args("-f").unwrap();
+        assert_eq!(args.get::<usize>("threads"), Some(&4));
+
+        let args = parse_args("-t 16").unwrap();
+        assert_eq!(args.get::<usize>("threads"), Some(&16));
+    }
+
+    crate::choice!(
+        enum Color {
+            Auto = "auto",
+            Always = "always",
+            Never = "never",
+        }
+    );
+    arg!(ColorArg: "color", 'c' -> Color = "auto");
+    arg!(Colors: "colors" -> Vec<Color>);
+
+    fn parse_choices(args: &str) -> Result<CliArgs> {
+        parse_with::<(ColorArg, Colors)>(&App::default(), args)
+    }
+
+    #[test]
+    fn choice_values() {
+        let args = parse_choices("-c always").unwrap();
+        assert_eq!(args.get::<Color>("color"), Some(&Color::Always));
+
+        let args = parse_choices("").unwrap();
+        assert_eq!(args.get::<Color>("color"), Some(&Color::Auto));
+
+        let args = parse_choices("--colors never --colors=auto").unwrap();
+        assert_eq!(
+            args.get::<Vec<Color>>("colors"),
+            Some(&vec![Color::Never, Color::Auto])
+        );
+    }
+
+    #[test]
+    fn invalid_choice() {
+        let err = parse_choices("--color blue").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::InvalidValue {
+                long: "color",
+                value: "blue".to_string(),
+                kind: <Color as crate::CliReturnValue>::KIND,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "argument '--color' expected one of 'auto', 'always', 'never', but got 'blue'."
+        );
+
+        let err = parse_choices("--colors never --colors blue").unwrap_err();
+        assert_eq!(err.token(), Some("blue".as_ref()));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Port(u16);
+
+    impl FromStr for Port {
+        type Err = std::num::ParseIntError;
+
+        fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+            s.parse().map(Port)
+        }
+    }
+
+    impl crate::CliValue for Port {}
+
+    arg!(PortArg: "port", 'p' -> Port);
+
+    #[test]
+    fn from_str_values() {
+        let parse = |args: &str| parse_with::<PortArg>(&App::default(), args);
+
+        let args = parse("-p 8080").unwrap();
+        assert_eq!(args.get::<Port>("port"), Some(&Port(8080)));
+
+        let err = parse("--port=http").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::ParseFailed {
+                long: "port",
+                value: "http".to_string(),
+                message: "invalid digit found in string".to_string(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "argument '--port' got invalid value 'http': invalid digit found in string."
+        );
+    }
+
+    arg!(Port16: "port", 'p' -> u16);
+    arg!(Hosts: "host" -> Vec<std::net::IpAddr>);
+
+    #[test]
+    fn std_values() {
+        let parse = |args: &str| parse_with::<(Port16, Hosts)>(&App::default(), args);
+
+        let args = parse("-p 8080 --host 127.0.0.1 --host ::1").unwrap();
+        assert_eq!(args.get::<u16>("port"), Some(&8080));
+        assert_eq!(
+            args.get::<Vec<std::net::IpAddr>>("host"),
+            Some(&vec!["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()])
+        );
+
+        let err = parse("-p 70000").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "argument '--port' got invalid value '70000': number too large to fit in target type."
+        );
+    }
+
+    arg!(PathArg: "path", 'p' -> PathBuf);
+    arg!(Raw: "raw" -> Vec<OsString>);
+
+    #[cfg(unix)]
+    fn non_utf8() -> OsString {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(vec![b'f', 0xff, b'o'])
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn non_utf8_values() {
+        let schema = Schema::create::<(PathArg, (Raw, OutFile))>().unwrap();
+        let parse = |args: Vec<OsString>| from_args(&schema, &App::default(), args.into_iter());
+
+        let args = parse(vec![
+            "-p".into(),
+            non_utf8(),
+            "--raw".into(),
+            non_utf8(),
+            non_utf8(),
+        ])
+        .unwrap();
+        assert_eq!(
+            args.get::<PathBuf>("path"),
+            Some(&PathBuf::from(non_utf8()))
+        );
+        assert_eq!(args.get::<Vec<OsString>>("raw"), Some(&vec![non_utf8()]));
+        assert_eq!(args.unnamed_os(), &[non_utf8()]);
+        assert_eq!(args.unnamed(), &["f\u{fffd}o"]);
+
+        // the arguments after `--` are kept as they are, so they can be passed on
+        let args = parse(vec![non_utf8(), "--".into(), non_utf8(), "-p".into()]).unwrap();
+        assert_eq!(args.leading_os(), &[non_utf8()]);
+        assert_eq!(args.trailing_os().unwrap(), &[non_utf8(), "-p".into()]);
+        assert_eq!(args.trailing().unwrap(), &["f\u{fffd}o", "-p"]);
+
+        let err = parse(vec!["-o".into(), non_utf8()]).unwrap_err();
+        assert_eq!(err, CallError::InvalidUtf8(non_utf8()));
+
+        // the value can also be in the same token as the flag
+        let join = |flag: &str| {
+            let mut token = OsString::from(flag);
+            token.push(non_utf8());
+            token
+        };
+        for token in [join("--path="), join("-p")] {
+            let args = parse(vec![token]).unwrap();
+            assert_eq!(
+                args.get::<PathBuf>("path"),
+                Some(&PathBuf::from(non_utf8()))
+            );
+        }
+        let err = parse(vec![join("-o")]).unwrap_err();
+        assert_eq!(err, CallError::InvalidUtf8(non_utf8()));
+        let err = parse(vec![join("--output=")]).unwrap_err();
+        assert_eq!(err, CallError::InvalidUtf8(non_utf8()));
+
+        let mut flag = OsString::from("--");
+        flag.push(non_utf8());
+        let err = parse(vec![flag.clone()]).unwrap_err();
+        assert_eq!(err, CallError::InvalidUtf8(flag));
+    }
+
+    #[test]
+    fn path_values() {
+        let args = parse_with::<PathArg>(&App::default(), "--path=src/main.rs -- -p").unwrap();
+        assert_eq!(
+            args.get::<PathBuf>("path"),
+            Some(&PathBuf::from("src/main.rs"))
+        );
+        assert_eq!(args.unnamed_os(), &["-p"]);
+    }
+
+    crate::command!(Build: "build" -> (Gentle, Include));
+    crate::command!(Remote: "remote" -> crate::Subcommands<(Add, PortCommand)>);
+    crate::command!(Add: "add" -> Source);
+    crate::command!(PortCommand: "port" -> PortArg);
+
+    type SubcommandArgs = (Force, crate::Subcommands<(Build, Remote)>);
+
+    fn parse_subcommands(args: &str) -> Result<CliArgs> {
+        parse_with::<SubcommandArgs>(&App::default(), args)
+    }
+
+    #[test]
+    fn subcommand() {
+        let mut args = parse_subcommands("-f build -g -I src main.c").unwrap();
+        assert_eq!(args.get::<bool>("force"), Some(&true));
+        assert_eq!(args.unnamed(), &[] as &[String]);
+
+        let (name, build) = args.take_subcommand().unwrap();
+        assert_eq!(name, "build");
+        assert_eq!(build.get::<bool>("gentle"), Some(&true));
+        assert_eq!(
+            build.get::<Vec<String>>("include"),
+            Some(&vec!["src".to_string()])
+        );
+        assert_eq!(build.unnamed(), &["main.c"]);
+
+        let mut args = parse_subcommands("-f").unwrap();
+        assert!(args.take_subcommand().is_none());
+    }
+
+    #[test]
+    fn global_after_subcommand() {
+        let mut args = parse_subcommands("build -gf").unwrap();
+        assert_eq!(args.get::<bool>("force"), Some(&true));
+        let (_, build) = args.take_subcommand().unwrap();
+        assert_eq!(build.get::<bool>("gentle"), Some(&true));
+        assert_eq!(build.get::<bool>("force"), None);
+
+        // the arguments of a subcommand are not available outside of it
+        let err = parse_subcommands("-g build").unwrap_err();
+        assert!(matches!(
+            err,
+            CallError::ShortFlagNotFound { flag: 'g', .. }
+        ));
+    }
+
+    #[test]
+    fn nested_subcommands() {
+        let mut args = parse_subcommands("remote add --force -s origin").unwrap();
+        assert_eq!(args.get::<bool>("force"), Some(&true));
+        let (name, mut remote) = args.take_subcommand().unwrap();
+        assert_eq!(name, "remote");
+        let (name, add) = remote.take_subcommand().unwrap();
+        assert_eq!(name, "add");
+        assert_eq!(add.get::<String>("source"), Some(&"origin".to_string()));
+
+        // values are converted inside of subcommands too
+        let mut args = parse_subcommands("remote port -p 22").unwrap();
+        let (_, mut remote) = args.take_subcommand().unwrap();
+        let (_, port) = remote.take_subcommand().unwrap();
+        assert_eq!(port.get::<Port>("port"), Some(&Port(22)));
+
+        let err = parse_subcommands("remote add").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::MissingRequired {
+                longs: vec!["source"],
+                positionals: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn subcommand_not_found() {
+        let err = parse_subcommands("buld").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::SubcommandNotFound {
+                name: "buld".to_string(),
+                suggestions: vec!["build".to_string()],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "subcommand 'buld' does not exist, did you mean 'build'?"
+        );
+    }
+
+    #[test]
+    fn subcommand_error_path() {
+        let schema = Schema::create::<SubcommandArgs>().unwrap();
+        let parse =
+            |args: &str| CliArgs::from_args(&schema, &App::default(), tokens(args)).unwrap_err();
+
+        let (err, path) = parse("build --jbs 3");
+        assert!(matches!(err, CallError::LongFlagNotFound { .. }));
+        assert_eq!(path, vec!["build"]);
+
+        let (err, path) = parse("remote add");
+        assert_eq!(
+            err,
+            CallError::MissingRequired {
+                longs: vec!["source"],
+                positionals: vec![]
+            }
+        );
+        assert_eq!(path, vec!["remote", "add"]);
+
+        let (_, path) = parse("-x build");
+        assert_eq!(path, Vec::<&str>::new());
+    }
+
+    #[test]
+    fn subcommand_help() {
+        let err = parse_subcommands("--help").unwrap_err();
+        assert_eq!(err, CallError::HelpPage(vec![]));
+        let err = parse_subcommands("remote add -h").unwrap_err();
+        assert_eq!(err, CallError::HelpPage(vec!["remote", "add"]));
+    }
+
+    crate::positional!(Sources: "source" -> Vec<PathBuf>, many);
+    crate::positional!(Dest: "dest" -> PathBuf);
+    crate::positional!(Mode: "mode" -> usize, optional);
+    crate::positional!(PortPositional: "port" -> Port, optional);
+
+    fn parse_positionals(args: &str) -> Result<CliArgs> {
+        type S = (Force, crate::Positionals<(Mode, (Sources, Dest))>);
+        parse_with::<S>(&App::default(), args)
+    }
+
+    #[test]
+    fn positionals() {
+        let args = parse_positionals("a -f b").unwrap();
+        assert_eq!(args.get::<usize>("mode"), None);
+        assert_eq!(
+            args.get::<Vec<PathBuf>>("source"),
+            Some(&vec![PathBuf::from("a")])
+        );
+        assert_eq!(args.get::<PathBuf>("dest"), Some(&PathBuf::from("b")));
+        assert_eq!(args.unnamed(), &["a", "b"]);
+
+        let args = parse_positionals("755 a b -- -c").unwrap();
+        assert_eq!(args.get::<usize>("mode"), Some(&755));
+        assert_eq!(
+            args.get::<Vec<PathBuf>>("source"),
+            Some(&vec![PathBuf::from("a"), PathBuf::from("b")])
+        );
+        assert_eq!(args.get::<PathBuf>("dest"), Some(&PathBuf::from("-c")));
+    }
+
+    #[test]
+    fn missing_positionals() {
+        let err = parse_positionals("a").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::MissingRequired {
+                longs: vec![],
+                positionals: vec!["dest"]
+            }
+        );
+
+        let err = parse_positionals("-f").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::MissingRequired {
+                longs: vec![],
+                positionals: vec!["source", "dest"]
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "missing required arguments: '<source>', '<dest>'."
+        );
+
+        // missing flags are reported together with the missing positional arguments
+        let err =
+            parse_with::<(Target, crate::Positionals<Dest>)>(&App::default(), "").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::MissingRequired {
+                longs: vec!["target"],
+                positionals: vec!["dest"]
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "missing required arguments: '--target', '<dest>'."
+        );
+    }
+
+    #[test]
+    fn invalid_positionals() {
+        let err = parse_positionals("rwx a b").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::InvalidPositional {
+                name: "mode",
+                value: "rwx".to_string(),
+                reason: "expected positive integer value".to_string(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "argument '<mode>' got invalid value 'rwx': expected positive integer value."
+        );
+
+        let parse =
+            |args: &str| parse_with::<crate::Positionals<PortPositional>>(&App::default(), args);
+        assert_eq!(parse("22").unwrap().get::<Port>("port"), Some(&Port(22)));
+        assert!(matches!(
+            parse("ssh").unwrap_err(),
+            CallError::InvalidPositional { name: "port", .. }
+        ));
+        assert_eq!(
+            parse("22 80").unwrap_err(),
+            CallError::UnexpectedPositional {
+                value: "80".to_string()
+            }
+        );
+    }
+
+    arg!(EnvJobs: "env-jobs", 'j' -> usize = 1, env = "BADARGS_TEST_JOBS");
+    arg!(EnvForce: "env-force" -> bool, env = "BADARGS_TEST_FORCE");
+    arg!(EnvPort: "env-port" -> Port, env = "BADARGS_TEST_PORT");
+    arg!(EnvUnset: "env-unset" -> String, required, env = "BADARGS_TEST_UNSET");
+
+    fn parse_env(args: &str) -> Result<CliArgs> {
+        parse_with::<(EnvJobs, (EnvForce, EnvPort))>(&App::default(), args)
+    }
+
+    // all environment variables are set in a single test, since the tests run in parallel
+    #[test]
+    fn env_values() {
+        std::env::set_var("BADARGS_TEST_JOBS", "8");
+        std::env::set_var("BADARGS_TEST_FORCE", "1");
+        std::env::set_var("BADARGS_TEST_PORT", "22");
+
+        let args = parse_env("").unwrap();
+        assert_eq!(args.get::<usize>("env-jobs"), Some(&8));
+        assert_eq!(
+            args.source("env-jobs"),
+            Some(ValueSource::Environment("BADARGS_TEST_JOBS"))
+        );
+        assert_eq!(args.get::<bool>("env-force"), Some(&true));
+        assert_eq!(args.get::<Port>("env-port"), Some(&Port(22)));
+
+        // the command line takes precedence
+        let args = parse_env("-j 2").unwrap();
+        assert_eq!(args.get::<usize>("env-jobs"), Some(&2));
+        assert_eq!(args.source("env-jobs"), Some(ValueSource::CommandLine));
+
+        std::env::set_var("BADARGS_TEST_FORCE", "0");
+        let args = parse_env("").unwrap();
+        assert_eq!(args.get::<bool>("env-force"), None);
+        assert_eq!(args.source("env-force"), None);
+
+        std::env::set_var("BADARGS_TEST_JOBS", "many");
+        let err = parse_env("").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::FromEnv {
+                var: "BADARGS_TEST_JOBS",
+                err: Box::new(CallError::InvalidValue {
+                    long: "env-jobs",
+                    value: "many".to_string(),
+                    kind: SchemaKind::UInt,
+                }),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "environment variable 'BADARGS_TEST_JOBS' is invalid: argument '--env-jobs' expected positive integer value, but got 'many'."
+        );
+        let inner = "argument '--env-jobs' expected positive integer value, but got 'many'.";
+        let source = std::error::Error::source(&err).map(ToString::to_string);
+        assert_eq!(source.as_deref(), Some(inner));
+        let err = crate::Error::Call(err);
+        let source = std::error::Error::source(&err).map(ToString::to_string);
+        assert_eq!(source.as_deref(), Some(inner));
+        std::env::set_var("BADARGS_TEST_JOBS", "8");
+
+        std::env::set_var("BADARGS_TEST_PORT", "ssh");
+        let err = parse_env("").unwrap_err();
+        assert!(matches!(
+            err,
+            CallError::FromEnv {
+                var: "BADARGS_TEST_PORT",
+                ..
+            }
+        ));
+        assert_eq!(err.token(), Some("ssh".as_ref()));
+
+        std::env::remove_var("BADARGS_TEST_JOBS");
+        std::env::remove_var("BADARGS_TEST_FORCE");
+        std::env::remove_var("BADARGS_TEST_PORT");
+        let args = parse_env("").unwrap();
+        assert_eq!(args.get::<usize>("env-jobs"), Some(&1));
+        assert_eq!(args.source("env-jobs"), Some(ValueSource::Default));
+    }
+
+    #[test]
+    fn env_missing() {
+        let err = parse_with::<EnvUnset>(&App::default(), "").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::MissingRequired {
+                longs: vec!["env-unset"],
+                positionals: vec![]
+            }
+        );
+    }
+
+    fn parse_config(file: &str, config: &str, args: &str) -> Result<CliArgs> {
+        let path = std::env::temp_dir().join(file);
+        std::fs::write(&path, config).unwrap();
+        let result = parse_with::<SubcommandArgs>(&App::default().config_file(&path), args);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn config_values() {
+        let config = "force = true\n\
+                      [build]\n\
+                      include = src\n\
+                      include = 'vendor dir'\n\
+                      gentle = false\n\
+                      [remote.port]\n\
+                      port = 22\n";
+
+        let mut args = parse_config("badargs-test-values.conf", config, "build").unwrap();
+        assert_eq!(args.get::<bool>("force"), Some(&true));
+        assert_eq!(
+            args.source("force"),
+            Some(ValueSource::ConfigFile { line: 1 })
+        );
+        let (_, build) = args.take_subcommand().unwrap();
+        assert_eq!(
+            build.get::<Vec<String>>("include"),
+            Some(&vec!["src".to_string(), "vendor dir".to_string()])
+        );
+        assert_eq!(
+            build.source("include"),
+            Some(ValueSource::ConfigFile { line: 4 })
+        );
+        // a flag that is turned off has neither a value nor a source
+        assert_eq!(build.get::<bool>("gentle"), None);
+        assert_eq!(build.source("gentle"), None);
+
+        let mut args = parse_config("badargs-test-nested.conf", config, "remote port").unwrap();
+        let (_, mut remote) = args.take_subcommand().unwrap();
+        let (_, port) = remote.take_subcommand().unwrap();
+        assert_eq!(port.get::<Port>("port"), Some(&Port(22)));
+
+        // the command line takes precedence
+        let mut args = parse_config("badargs-test-override.conf", config, "build -I main").unwrap();
+        let (_, build) = args.take_subcommand().unwrap();
+        assert_eq!(
+            build.get::<Vec<String>>("include"),
+            Some(&vec!["main".to_string()])
+        );
+        assert_eq!(build.source("include"), Some(ValueSource::CommandLine));
+    }
+
+    #[test]
+    fn config_errors() {
+        let err = parse_config("badargs-test-unknown.conf", "\nforce = yes", "").unwrap_err();
+        assert!(matches!(
+            err,
+            CallError::FromConfig {
+                line: 2,
+                ref err,
+                ..
+            } if matches!(**err, CallError::InvalidValue { long: "force", .. })
+        ));
+        assert_eq!(err.flag(), Some("--force".to_string()));
+
+        let err = parse_config(
+            "badargs-test-port.conf",
+            "[remote.port]\nport = ssh",
+            "remote port",
+        )
+        .unwrap_err();
+        assert!(matches!(err, CallError::FromConfig { line: 2, .. }));
+        assert_eq!(err.token(), Some("ssh".as_ref()));
+
+        let err = parse_config("badargs-test-section.conf", "gentle = true", "").unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("badargs-test-section.conf:1: argument 'gentle' does not exist."));
+
+        // the help page still works, so the user can find out how to fix the config
+        let err = parse_config("badargs-test-help.conf", "garbage line", "--help").unwrap_err();
+        assert_eq!(err, CallError::HelpPage(vec![]));
+        let err = parse_config("badargs-test-broken.conf", "garbage line", "").unwrap_err();
+        assert!(matches!(err, CallError::ConfigFile { line: Some(1), .. }));
+    }
+
+    fn parse_required(args: &str) -> Result<CliArgs> {
+        parse_with::<(Source, (Target, Force))>(&App::default(), args)
+    }
+
+    #[test]
+    fn required_present() {
+        let args = parse_required("-s main.c --target main").unwrap();
+        assert_eq!(args.get::<String>("source"), Some(&"main.c".to_string()));
+        assert_eq!(args.get::<String>("target"), Some(&"main".to_string()));
+    }
+
+    #[test]
+    fn required_missing() {
+        let err = parse_required("-f").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::MissingRequired {
+                longs: vec!["source", "target"],
+                positionals: vec![]
+            }
+        );
+
+        let err = parse_required("--target main").unwrap_err();
+        assert_eq!(
+            err,
+            CallError::MissingRequired {
+                longs: vec!["source"],
+                positionals: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn required_help() {
+        let err = parse_required("--help").unwrap_err();
+        assert_eq!(err, CallError::HelpPage(vec![]));
+    }
+
+    #[test]
+    fn version() {
+        let app = App::new().version("1.0.0");
+        let parse = |args: &str| parse_with::<Force>(&app, args);
+        assert_eq!(parse("--version").unwrap_err(), CallError::Version);
+        assert_eq!(parse("-fV").unwrap_err(), CallError::Version);
+    }
+
+    #[test]
+    fn no_version() {
+        let err = parse_args("--version").unwrap_err();
+        assert!(matches!(err, CallError::LongFlagNotFound { .. }));
+    }
+
+    #[test]
+    fn short_help() {
+        assert_eq!(parse_args("-h").unwrap_err(), CallError::HelpPage(vec![]));
+        assert_eq!(parse_args("-fh").unwrap_err(), CallError::HelpPage(vec![]));
+    }
+
+    #[test]
+    fn help_flag_claimed_by_schema() {
+        let args = parse_with::<Host>(&App::new(), "-h localhost").unwrap();
+        assert_eq!(args.get::<String>("host"), Some(&"localhost".to_string()));
+    }
+
+    #[test]
+    fn renamed_help_flags() {
+        let app = App::new().help_flags(Some("usage"), Some('?'));
+        let parse = |args: &str| parse_with::<AllArgs>(&app, args);
+        assert_eq!(parse("--usage").unwrap_err(), CallError::HelpPage(vec![]));
+        assert_eq!(parse("-?").unwrap_err(), CallError::HelpPage(vec![]));
+        assert!(matches!(
+            parse("--help").unwrap_err(),
+            CallError::LongFlagNotFound { .. }
+        ));
+    }
+
+    #[test]
+    fn disabled_help_flags() {
+        let app = App::new().help_flags(None, None);
+        let err = parse_with::<AllArgs>(&app, "-h");
+        assert!(matches!(
+            err.unwrap_err(),
+            CallError::ShortFlagNotFound { flag: 'h', .. }
+        ));
+    }
+
+    #[test]
+    fn suggestions() {
+        let err = parse_args("--ouput main.c").unwrap_err();
+        assert!(matches!(
+            err,
+            CallError::LongFlagNotFound { ref suggestions, .. } if suggestions == &["--output"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "argument '--ouput' does not exist, did you mean '--output'?"
+        );
+
+        let err = parse_args("-F").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "argument '-F' does not exist, did you mean '-f'?"
+        );
+
+        let err = parse_args("-gentle").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "argument '-e' does not exist, did you mean '--gentle'?"
+        );
+
+        let err = parse_args("--zzz").unwrap_err();
+        assert_eq!(err.to_string(), "argument '--zzz' does not exist.");
+    }
 }
@@ -1,47 +1,593 @@
+use crate::app::App;
 use crate::error::CallError;
-use crate::schema::{Schema, SchemaKind};
+use crate::schema::{Schema, SchemaCommand, SchemaKind, SchemaPositional};
+use crate::Arity;
+use std::path::Path;
 
-pub fn report(err: CallError, schema: &Schema) -> ! {
+/// Prints the error or the requested page and exits the process
+///
+/// The help page and version go to stdout, errors go to stderr. The usage below an error is the one
+/// of the command reached by the `subcommands` that were entered
+pub fn report(
+    err: CallError,
+    subcommands: &[&'static str],
+    schema: &Schema,
+    app: &App,
+    program_name: Option<&str>,
+) -> ! {
+    let name = display_name(app, program_name);
     match err {
-        CallError::ShortFlagNotFound(arg) => println!("error: argument '{}' does not exist.", arg),
-        CallError::LongFlagNotFound(arg) => println!("error: argument '{}' does not exist.", arg), 
-        CallError::ExpectedValue(arg, kind) => {
-            println!(
-                "error: argument '{}' expected {} value, but got nothing.",
-                arg,
-                match kind {
-                    SchemaKind::String => "string",
-                    SchemaKind::Bool => unreachable!(),
-                    SchemaKind::IInt => "integer",
-                    SchemaKind::UInt => "positive integer",
-                    SchemaKind::Num => "number"
-                }
-            )
-        }
-        CallError::INan(arg) => println!("error: argument '{}' expected a positive integer value, but got an invalid positive integer.", arg),
-        CallError::UNan(arg) => println!("error: argument '{}' expected an integer value, but got an invalid integer.", arg),
-        CallError::NNan(arg) => println!("error: argument '{}' expected a number value, but got an invalid number.", arg),
-        CallError::CombinedShortWithValue(arg) => println!("error: using argument expecting value '{}' in position where only flags are allowed", arg),
-        CallError::InvalidUtf8(os_str) => println!("error: invalid utf8: '{}'", os_str.to_string_lossy()),
-        CallError::HelpPage => {
-            println!("Options:");
-            for option in schema.arguments() {
-                print!("--{} ", option.long);
-                if let Some(short) = option.short {
-                    print!("(-{}) ", short);
-                }
-                match option.kind {
-                    SchemaKind::String => print!("[Takes a value]"),
-                    SchemaKind::Bool => {}
-                    SchemaKind::IInt => print!("[Takes an integer]"),
-                    SchemaKind::UInt => print!("[Takes a positive integer]"),
-                    SchemaKind::Num => print!("[Takes a number]"),
-                }
-                println!();
-            }
-            std::process::exit(0);
+        CallError::HelpPage(subcommands) => {
+            print!("{}", help_page(schema, app, name, &subcommands));
+            std::process::exit(app.help_exit_code);
+        }
+        CallError::Version => {
+            println!("{}", version(app));
+            std::process::exit(app.help_exit_code);
+        }
+        err => {
+            eprintln!("error: {}", err);
+            eprintln!();
+            eprintln!("{}", command_usage(schema, name, subcommands));
+        }
+    }
+
+    std::process::exit(app.error_exit_code)
+}
+
+/// Renders the name and version of the program, like `compiler 1.0.0`
+pub fn version(app: &App) -> String {
+    let version = app.version.unwrap_or_default();
+    match app.name {
+        Some(name) => format!("{} {}", name, version),
+        None => version.to_string(),
+    }
+}
+
+/// The name of the program as shown in the usage, the file name it was invoked with or the name from the `App`
+///
+/// The invoked name comes first, since programs can be installed under a different name or be
+/// called through links with different names
+fn display_name<'a>(app: &'a App, program_name: Option<&'a str>) -> Option<&'a str> {
+    program_name
+        .map(|program_name| {
+            Path::new(program_name)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(program_name)
+        })
+        .or(app.name)
+}
+
+/// The name of the program followed by the `subcommands`, like `compiler build`
+fn command_name(name: Option<&str>, subcommands: &[&str]) -> Option<String> {
+    let name = name
+        .into_iter()
+        .chain(subcommands.iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(name).filter(|name| !name.is_empty())
+}
+
+/// Renders the compact usage line of the command reached by the `subcommands`
+pub fn command_usage(schema: &Schema, name: Option<&str>, subcommands: &[&str]) -> String {
+    let command = subcommands.iter().fold(schema, |schema, subcommand| {
+        &schema
+            .subcommand(subcommand)
+            .expect("subcommand of the usage does not exist")
+            .schema
+    });
+    usage(command, command_name(name, subcommands).as_deref(), true)
+}
+
+/// Renders the usage line, like `Usage: compiler [-f] [-o <value>] [ARGS...]`
+///
+/// The compact usage only lists the required arguments and replaces all others with `[OPTIONS]`
+pub fn usage(schema: &Schema, name: Option<&str>, compact: bool) -> String {
+    let mut usage = String::from("Usage:");
+    if let Some(name) = name {
+        usage.push(' ');
+        usage.push_str(name);
+    }
+
+    if compact && schema.arguments().any(|option| !option.required) {
+        usage.push_str(" [OPTIONS]");
+    }
+
+    for option in schema.arguments() {
+        if compact && !option.required {
+            continue;
+        }
+
+        let mut synopsis = match option.short {
+            Some(short) => format!("-{}", short),
+            None => format!("--{}", option.long),
+        };
+        if let Some(placeholder) = value_placeholder(option.kind) {
+            synopsis.push(' ');
+            // the `...` of repeatable values is put behind the brackets
+            synopsis.push_str(placeholder.trim_end_matches("..."));
+        }
+
+        usage.push(' ');
+        if option.required {
+            usage.push_str(&synopsis);
+        } else {
+            usage.push_str(&format!("[{}]", synopsis));
+        }
+        if let SchemaKind::Vec(_) | SchemaKind::Count = option.kind {
+            usage.push_str("...");
+        }
+    }
+
+    if !schema.subcommands().is_empty() {
+        usage.push_str(" [COMMAND]");
+    } else if schema.positionals().is_empty() {
+        usage.push_str(" [ARGS...]");
+    }
+    for positional in schema.positionals() {
+        usage.push(' ');
+        usage.push_str(&positional_synopsis(positional));
+    }
+    usage
+}
+
+/// Renders the help page of the command reached by the `subcommands`, with the flags and descriptions
+/// in aligned columns
+pub fn help_page(schema: &Schema, app: &App, name: Option<&str>, subcommands: &[&str]) -> String {
+    // the arguments of the outer commands are global
+    let mut globals = Vec::new();
+    let mut schema = schema;
+    let mut about = app.about;
+    for subcommand in subcommands {
+        globals.push(schema);
+        let subcommand = schema
+            .subcommand(subcommand)
+            .expect("subcommand of the help page does not exist");
+        schema = &subcommand.schema;
+        about = subcommand.description;
+    }
+
+    let rows = |schemas: &[&Schema]| {
+        let mut options = schemas
+            .iter()
+            .flat_map(|schema| schema.arguments())
+            .collect::<Vec<_>>();
+        if app.sort_help {
+            options.sort_by_key(|option| option.long);
+        }
+        options
+            .into_iter()
+            .map(|option| (flag_column(option), description_column(option)))
+            .collect::<Vec<_>>()
+    };
+    let sections = [
+        ("Commands:", command_rows(schema)),
+        ("Arguments:", positional_rows(schema)),
+        ("Options:", {
+            let mut options = rows(&[schema]);
+            let entered = globals
+                .iter()
+                .copied()
+                .chain(Some(schema))
+                .collect::<Vec<_>>();
+            options.extend(builtin_rows(app, &entered));
+            options
+        }),
+        ("Global options:", rows(&globals)),
+    ];
+
+    let width = sections
+        .iter()
+        .flat_map(|(_, rows)| rows)
+        .map(|(flags, _)| flags.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut page = String::new();
+    if subcommands.is_empty() && (app.name.is_some() || app.version.is_some()) {
+        page.push_str(version(app).trim());
+        page.push('\n');
+    }
+    if subcommands.is_empty() {
+        if let Some(author) = app.author.filter(|author| !author.is_empty()) {
+            page.push_str(author);
+            page.push('\n');
+        }
+    }
+    if let Some(about) = about.map(one_line).filter(|about| !about.is_empty()) {
+        page.push_str(&about);
+        page.push('\n');
+    }
+    if !page.is_empty() {
+        page.push('\n');
+    }
+
+    page.push_str(&usage(
+        schema,
+        command_name(name, subcommands).as_deref(),
+        false,
+    ));
+    page.push('\n');
+
+    for (title, rows) in sections.iter() {
+        if rows.is_empty() {
+            continue;
+        }
+        page.push('\n');
+        page.push_str(title);
+        page.push('\n');
+        for (flags, description) in rows {
+            let line = format!("  {:width$}  {}", flags, description, width = width);
+            page.push_str(line.trim_end());
+            page.push('\n');
+        }
+    }
+    page
+}
+
+fn command_rows(schema: &Schema) -> Vec<(String, String)> {
+    schema
+        .subcommands()
+        .iter()
+        .map(|subcommand| {
+            let description = subcommand.description.map(one_line).unwrap_or_default();
+            (subcommand.name.to_string(), description)
+        })
+        .collect()
+}
+
+fn positional_rows(schema: &Schema) -> Vec<(String, String)> {
+    schema
+        .positionals()
+        .iter()
+        .map(|positional| {
+            let description = positional.description.map(one_line).unwrap_or_default();
+            (positional_synopsis(positional), description)
+        })
+        .collect()
+}
+
+/// Renders a positional argument like `<input>`, `[<output>]` or `<files>...`
+fn positional_synopsis(positional: &SchemaPositional) -> String {
+    match positional.arity {
+        Arity::One => format!("<{}>", positional.name),
+        Arity::Optional => format!("[<{}>]", positional.name),
+        Arity::OneOrMore => format!("<{}>...", positional.name),
+    }
+}
+
+/// Joins the lines of a doc comment into a single line
+fn one_line(description: &str) -> String {
+    description
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The rows of the flags that are handled by badargs itself, unless the arguments of the `schemas`
+/// use them
+fn builtin_rows(app: &App, schemas: &[&Schema]) -> Vec<(String, String)> {
+    let mut builtins = vec![(app.help_short, app.help_long, "Print help")];
+    if app.version.is_some() {
+        builtins.push((Some('V'), Some("version"), "Print version"));
+    }
+
+    builtins
+        .into_iter()
+        .filter_map(|(short, long, description)| {
+            let short =
+                short.filter(|&short| schemas.iter().all(|schema| schema.short(short).is_none()));
+            let long = long.filter(|long| schemas.iter().all(|schema| schema.long(long).is_none()));
+            let flags = match (short, long) {
+                (Some(short), Some(long)) => format!("-{}, --{}", short, long),
+                (None, Some(long)) => format!("    --{}", long),
+                (Some(short), None) => format!("-{}", short),
+                (None, None) => return None,
+            };
+            Some((flags, description.to_string()))
+        })
+        .collect()
+}
+
+fn flag_column(option: &SchemaCommand) -> String {
+    let mut column = match option.short {
+        Some(short) => format!("-{}, --{}", short, option.long),
+        None => format!("    --{}", option.long),
+    };
+    if let Some(placeholder) = value_placeholder(option.kind) {
+        column.push(' ');
+        column.push_str(&placeholder);
+    }
+    column
+}
+
+fn description_column(option: &SchemaCommand) -> String {
+    let mut column = option.description.map(one_line).unwrap_or_default();
+
+    let mut push_note = |note: &str| {
+        if !column.is_empty() {
+            column.push(' ');
         }
+        column.push_str(note);
+    };
+
+    if let SchemaKind::Vec(_) | SchemaKind::Count = option.kind {
+        push_note("[can be repeated]");
+    }
+    if option.required {
+        push_note("[required]");
+    }
+    if let Some(var) = option.env {
+        push_note(&format!("[env: {}]", var));
+    }
+    if let Some(default) = option.default {
+        push_note(&format!("[default: {}]", default));
+    }
+    column
+}
+
+fn value_placeholder(kind: SchemaKind) -> Option<String> {
+    match kind {
+        SchemaKind::String | SchemaKind::OsString => Some("<value>".to_string()),
+        SchemaKind::Path => Some("<path>".to_string()),
+        SchemaKind::Bool | SchemaKind::Count => None,
+        SchemaKind::IInt => Some("<int>".to_string()),
+        SchemaKind::UInt => Some("<uint>".to_string()),
+        SchemaKind::Num => Some("<number>".to_string()),
+        SchemaKind::Vec(inner) => value_placeholder(*inner).map(|inner| inner + "..."),
+        SchemaKind::Custom {
+            choices: Some(choices),
+            ..
+        } => Some(format!("<{}>", choices.join("|"))),
+        SchemaKind::Custom { name, .. } => Some(format!("<{}>", name)),
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::arg;
+
+    arg!(
+        /// The file the output is written to
+        OutFile: "output", 'o' -> String
+    );
+    arg!(Force: "force", 'f' -> bool);
+    arg!(
+        /// The optimization level
+        OLevel: "optimize" -> usize = 0
+    );
+    arg!(Include: "include", 'I' -> Vec<String>, required);
+
+    #[test]
+    fn help_with_description() {
+        let schema = Schema::create::<OutFile>().unwrap();
+        assert_eq!(
+            help_page(&schema, &App::new(), None, &[]),
+            "Usage: [-o <value>] [ARGS...]\n\nOptions:\n  -o, --output <value>  The file the output is written to\n  -h, --help            Print help\n"
+        );
+    }
+
+    #[test]
+    fn help_with_app() {
+        let schema = Schema::create::<OutFile>().unwrap();
+        let app = App::new()
+            .name("compiler")
+            .version("1.0.0")
+            .about("Compiles things");
+        assert_eq!(
+            help_page(&schema, &app, Some("compiler"), &[]),
+            "compiler 1.0.0\nCompiles things\n\nUsage: compiler [-o <value>] [ARGS...]\n\nOptions:\n  -o, --output <value>  The file the output is written to\n  -h, --help            Print help\n  -V, --version         Print version\n"
+        );
+        assert_eq!(version(&app), "compiler 1.0.0");
+    }
+
+    #[test]
+    fn help_declaration_order() {
+        let schema = Schema::create::<(OutFile, (Force, (OLevel, Include)))>().unwrap();
+        assert_eq!(
+            help_page(&schema, &App::new(), Some("compiler"), &[]),
+            "\
+Usage: compiler [-o <value>] [-f] [--optimize <uint>] -I <value>... [ARGS...]
+
+Options:
+  -o, --output <value>      The file the output is written to
+  -f, --force
+      --optimize <uint>     The optimization level [default: 0]
+  -I, --include <value>...  [can be repeated] [required]
+  -h, --help                Print help
+"
+        );
+    }
+
+    #[test]
+    fn help_sorted() {
+        let schema = Schema::create::<(OutFile, (Force, (OLevel, Include)))>().unwrap();
+        assert_eq!(
+            help_page(&schema, &App::new().sort_help(true), Some("compiler"), &[]),
+            "\
+Usage: compiler [-o <value>] [-f] [--optimize <uint>] -I <value>... [ARGS...]
+
+Options:
+  -f, --force
+  -I, --include <value>...  [can be repeated] [required]
+      --optimize <uint>     The optimization level [default: 0]
+  -o, --output <value>      The file the output is written to
+  -h, --help                Print help
+"
+        );
+    }
+
+    #[test]
+    fn help_choices() {
+        crate::choice!(
+            enum Color {
+                Auto = "auto",
+                Never = "never",
+            }
+        );
+        arg!(ColorArg: "color" -> Color = "auto");
 
-    std::process::exit(1)
+        let schema = Schema::create::<ColorArg>().unwrap();
+        assert_eq!(
+            help_page(&schema, &App::new(), None, &[]),
+            "Usage: [--color <auto|never>] [ARGS...]\n\nOptions:\n      --color <auto|never>  [default: auto]\n  -h, --help                Print help\n"
+        );
+    }
+
+    #[test]
+    fn help_subcommands() {
+        crate::command!(
+            /// Compile the project
+            Build: "build" -> (OutFile, OLevel)
+        );
+        crate::command!(Clean: "clean" -> ());
+
+        let schema = Schema::create::<(Force, crate::Subcommands<(Build, Clean)>)>().unwrap();
+        let app = App::new().about("Builds things");
+        assert_eq!(
+            help_page(&schema, &app, Some("cargo"), &[]),
+            "\
+Builds things
+
+Usage: cargo [-f] [COMMAND]
+
+Commands:
+  build        Compile the project
+  clean
+
+Options:
+  -f, --force
+  -h, --help   Print help
+"
+        );
+        assert_eq!(
+            help_page(&schema, &app, Some("cargo"), &["build"]),
+            "\
+Compile the project
+
+Usage: cargo build [-o <value>] [--optimize <uint>] [ARGS...]
+
+Options:
+  -o, --output <value>   The file the output is written to
+      --optimize <uint>  The optimization level [default: 0]
+  -h, --help             Print help
+
+Global options:
+  -f, --force
+"
+        );
+    }
+
+    #[test]
+    fn help_positionals() {
+        crate::positional!(
+            /// The files to copy
+            Sources: "source" -> Vec<String>, many
+        );
+        crate::positional!(Dest: "dest" -> String, optional);
+
+        let schema = Schema::create::<(Force, crate::Positionals<(Sources, Dest)>)>().unwrap();
+        assert_eq!(
+            help_page(&schema, &App::new(), Some("cp"), &[]),
+            "\
+Usage: cp [-f] <source>... [<dest>]
+
+Arguments:
+  <source>...  The files to copy
+  [<dest>]
+
+Options:
+  -f, --force
+  -h, --help   Print help
+"
+        );
+        assert_eq!(
+            usage(&schema, Some("cp"), true),
+            "Usage: cp [OPTIONS] <source>... [<dest>]"
+        );
+    }
+
+    #[test]
+    fn help_env() {
+        arg!(
+            /// The access token
+            Token: "token" -> String, env = "APP_TOKEN"
+        );
+        arg!(Jobs: "jobs" -> usize = 1, env = "APP_JOBS");
+
+        let schema = Schema::create::<(Token, Jobs)>().unwrap();
+        assert_eq!(
+            help_page(&schema, &App::new(), None, &[]),
+            "\
+Usage: [--token <value>] [--jobs <uint>] [ARGS...]
+
+Options:
+      --token <value>  The access token [env: APP_TOKEN]
+      --jobs <uint>    [env: APP_JOBS] [default: 1]
+  -h, --help           Print help
+"
+        );
+    }
+
+    #[test]
+    fn help_builtin_flags() {
+        arg!(Host: "host", 'h' -> String);
+        arg!(ShowVersion: "version" -> bool);
+
+        let schema = Schema::create::<(Host, ShowVersion)>().unwrap();
+        assert_eq!(
+            help_page(&schema, &App::new().version("1.0.0"), None, &[]),
+            "\
+1.0.0
+
+Usage: [-h <value>] [--version] [ARGS...]
+
+Options:
+  -h, --host <value>
+      --version
+      --help          Print help
+  -V                  Print version
+"
+        );
+
+        let schema = Schema::create::<Force>().unwrap();
+        let app = App::new().help_flags(Some("usage"), None);
+        assert_eq!(
+            help_page(&schema, &app, None, &[]),
+            "Usage: [-f] [ARGS...]\n\nOptions:\n  -f, --force\n      --usage  Print help\n"
+        );
+        let app = App::new().help_flags(None, None);
+        assert_eq!(
+            help_page(&schema, &app, None, &[]),
+            "Usage: [-f] [ARGS...]\n\nOptions:\n  -f, --force\n"
+        );
+    }
+
+    #[test]
+    fn compact_usage() {
+        let schema = Schema::create::<(OutFile, (Force, (OLevel, Include)))>().unwrap();
+        assert_eq!(
+            usage(&schema, Some("compiler"), true),
+            "Usage: compiler [OPTIONS] -I <value>... [ARGS...]"
+        );
+    }
+
+    #[test]
+    fn usage_program_name() {
+        let app = App::new();
+        assert_eq!(
+            display_name(&app, Some("/usr/bin/compiler")),
+            Some("compiler")
+        );
+        let app = App::new().name("cc");
+        assert_eq!(
+            display_name(&app, Some("/usr/bin/compiler")),
+            Some("compiler")
+        );
+        assert_eq!(display_name(&app, None), Some("cc"));
+    }
 }
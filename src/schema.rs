@@ -4,20 +4,61 @@
 //! This makes the interface of this crate fully type-safe! (and kind of cursed)
 
 use super::Result;
-use crate::{CliArg, CliReturnValue, SchemaError};
+use crate::parse::{self, CliArgs};
+use crate::sealed::SealedCliReturnValue;
+use crate::{
+    Arity, CallError, CliArg, CliCommand, CliPositional, CliReturnValue, Positionals, SchemaError,
+    Subcommands,
+};
 use std::collections::HashMap;
 
 ///
 /// The type of value the argument returns
 ///
-/// This could *maybe* also be solved with trait objects but lets keep this for now
+/// It is part of some [`CallError`]s, to tell what kind of value was expected
+/// ```
+/// use badargs::{arg, CallError, Error, SchemaKind};
+///
+/// arg!(Jobs: "jobs", 'j' -> usize);
+///
+/// let err = badargs::try_badargs_from::<Jobs, _>(["make", "-j", "many"]).unwrap_err();
+/// assert!(matches!(
+///     err,
+///     Error::Call(CallError::InvalidValue { kind: SchemaKind::UInt, .. })
+/// ));
+/// ```
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum SchemaKind {
     String,
+    /// A string that does not have to be valid UTF-8
+    OsString,
+    /// A path that does not have to be valid UTF-8
+    Path,
+    /// A flag without a value
     Bool,
+    /// An `isize`
     IInt,
+    /// A `usize`
     UInt,
+    /// An `f64`
     Num,
+    /// The argument can be repeated, the values of the inner kind are collected
+    Vec(&'static SchemaKind),
+    /// A flag that counts how often it was given
+    Count,
+    /// A value of a [`CliValue`](crate::CliValue) type, optionally restricted to some choices
+    Custom {
+        name: &'static str,
+        choices: Option<&'static [&'static str]>,
+    },
+}
+
+impl SchemaKind {
+    /// Whether the argument is followed by a value
+    pub const fn takes_value(&self) -> bool {
+        !matches!(self, SchemaKind::Bool | SchemaKind::Count)
+    }
 }
 
 ///
@@ -27,6 +68,31 @@ pub struct SchemaCommand {
     pub kind: SchemaKind,
     pub long: &'static str,
     pub short: Option<char>,
+    /// The value used when the argument is not provided, parsed like a value from the command line
+    pub default: Option<&'static str>,
+    pub required: bool,
+    pub description: Option<&'static str>,
+    /// The environment variable used when the argument is not provided
+    pub env: Option<&'static str>,
+}
+
+///
+/// A positional argument in the schema
+#[derive(Debug, Clone, Eq, PartialEq, Copy)]
+pub struct SchemaPositional {
+    pub kind: SchemaKind,
+    pub name: &'static str,
+    pub arity: Arity,
+    pub description: Option<&'static str>,
+}
+
+///
+/// A subcommand in the schema, with the schema of its own arguments
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Subcommand {
+    pub name: &'static str,
+    pub description: Option<&'static str>,
+    pub schema: Schema,
 }
 
 ///
@@ -35,6 +101,12 @@ pub struct SchemaCommand {
 pub struct Schema {
     longs: HashMap<&'static str, SchemaCommand>,
     shorts: HashMap<char, SchemaCommand>,
+    /// The long names in the order they were declared in
+    order: Vec<&'static str>,
+    /// The positional arguments in the order they were declared in
+    positionals: Vec<SchemaPositional>,
+    /// The subcommands in the order they were declared in
+    subcommands: Vec<Subcommand>,
 }
 
 impl Schema {
@@ -45,17 +117,36 @@ impl Schema {
     {
         let mut schema = Schema::default();
         S::add_schema(&mut schema)?;
+        schema.check_defaults()?;
+        if !schema.positionals.is_empty() && !schema.subcommands.is_empty() {
+            return Err(SchemaError::InvalidSchema(
+                "a command can't have both positional arguments and subcommands".to_string(),
+            ));
+        }
         Ok(schema)
     }
 
     fn add_command(&mut self, long_name: &'static str, command: SchemaCommand) -> Result<()> {
-        if self.longs.insert(long_name, command).is_some() {
+        // positional arguments are stored under their name, next to the long names
+        if self.positional(long_name).is_some() || self.longs.insert(long_name, command).is_some() {
             Err(SchemaError::NameAlreadyExists(long_name.to_string()))
         } else {
+            self.order.push(long_name);
             Ok(())
         }
     }
 
+    fn check_defaults(&self) -> Result<()> {
+        for command in self.arguments() {
+            if let Some(default) = command.default {
+                parse::check_value(command, default).map_err(|err| {
+                    SchemaError::InvalidSchema(format!("invalid default value: {}", err))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn short(&self, name: char) -> Option<&SchemaCommand> {
         self.shorts.get(&name)
     }
@@ -64,8 +155,62 @@ impl Schema {
         self.longs.get(name)
     }
 
+    /// All arguments, in the order they were declared in
     pub fn arguments(&self) -> impl Iterator<Item = &SchemaCommand> {
-        self.longs.values()
+        self.order.iter().map(move |long| &self.longs[long])
+    }
+
+    /// All positional arguments, in the order they were declared in
+    pub fn positionals(&self) -> &[SchemaPositional] {
+        &self.positionals
+    }
+
+    fn positional(&self, name: &str) -> Option<&SchemaPositional> {
+        self.positionals
+            .iter()
+            .find(|positional| positional.name == name)
+    }
+
+    fn add_positional(&mut self, positional: SchemaPositional) -> Result<()> {
+        if self.positional(positional.name).is_some() || self.longs.contains_key(positional.name) {
+            return Err(SchemaError::NameAlreadyExists(positional.name.to_string()));
+        }
+        if !positional.kind.takes_value() {
+            return Err(SchemaError::InvalidSchema(format!(
+                "positional argument '{}' does not take a value",
+                positional.name
+            )));
+        }
+        let repeated = matches!(positional.kind, SchemaKind::Vec(_));
+        if repeated != (positional.arity == Arity::OneOrMore) {
+            return Err(SchemaError::InvalidSchema(format!(
+                "positional argument '{}' has to be a Vec exactly if it takes one or more values",
+                positional.name
+            )));
+        }
+        self.positionals.push(positional);
+        Ok(())
+    }
+
+    /// The subcommand with this name
+    pub fn subcommand(&self, name: &str) -> Option<&Subcommand> {
+        self.subcommands
+            .iter()
+            .find(|subcommand| subcommand.name == name)
+    }
+
+    /// All subcommands, in the order they were declared in
+    pub fn subcommands(&self) -> &[Subcommand] {
+        &self.subcommands
+    }
+
+    fn add_subcommand(&mut self, subcommand: Subcommand) -> Result<()> {
+        if self.subcommand(subcommand.name).is_some() {
+            Err(SchemaError::NameAlreadyExists(subcommand.name.to_string()))
+        } else {
+            self.subcommands.push(subcommand);
+            Ok(())
+        }
     }
 
     fn add_short_command(&mut self, short_name: char, command: SchemaCommand) -> Result<()> {
@@ -86,6 +231,9 @@ impl Schema {
 /// multiple arguments
 pub trait IntoSchema {
     fn add_schema(schema: &mut Schema) -> Result<()>;
+
+    /// Converts the values stored while parsing into the types of the arguments
+    fn convert(args: &mut CliArgs) -> std::result::Result<(), CallError>;
 }
 
 /// Allow using multiple schema values, these tuples can be nested :D
@@ -98,6 +246,53 @@ where
         S1::add_schema(schema)?;
         S2::add_schema(schema)
     }
+
+    fn convert(args: &mut CliArgs) -> std::result::Result<(), CallError> {
+        S1::convert(args)?;
+        S2::convert(args)
+    }
+}
+
+/// A command without any arguments
+impl IntoSchema for () {
+    fn add_schema(_: &mut Schema) -> Result<()> {
+        Ok(())
+    }
+
+    fn convert(_: &mut CliArgs) -> std::result::Result<(), CallError> {
+        Ok(())
+    }
+}
+
+/// Add the positional arguments to the schema
+impl<P> IntoSchema for Positionals<P>
+where
+    P: IntoPositionals,
+{
+    fn add_schema(schema: &mut Schema) -> Result<()> {
+        P::add_positionals(schema)
+    }
+
+    fn convert(args: &mut CliArgs) -> std::result::Result<(), CallError> {
+        P::convert(args)
+    }
+}
+
+/// Add the subcommands to the schema
+impl<C> IntoSchema for Subcommands<C>
+where
+    C: IntoCommands,
+{
+    fn add_schema(schema: &mut Schema) -> Result<()> {
+        C::add_commands(schema)
+    }
+
+    fn convert(args: &mut CliArgs) -> std::result::Result<(), CallError> {
+        match args.subcommand_mut() {
+            Some((name, args)) => C::convert(name, args),
+            None => Ok(()),
+        }
+    }
 }
 
 /// Create the Schema from the CliArg type
@@ -108,15 +303,122 @@ where
     fn add_schema(schema: &mut Schema) -> Result<()> {
         let short = T::short();
         let command = SchemaCommand {
-            kind: T::Content::kind(),
+            kind: <T::Content as CliReturnValue>::KIND,
             long: T::long(),
             short,
+            default: T::default_value(),
+            required: T::required(),
+            description: T::description(),
+            env: T::env(),
         };
+        if let Some(default) = command.default {
+            <T::Content as SealedCliReturnValue>::check_default(T::long(), default).map_err(
+                |err| SchemaError::InvalidSchema(format!("invalid default value: {}", err)),
+            )?;
+        }
         if let Some(short) = short {
             schema.add_short_command(short, command)?;
         }
         schema.add_command(T::long(), command)
     }
+
+    fn convert(args: &mut CliArgs) -> std::result::Result<(), CallError> {
+        args.convert(T::long(), <T::Content as SealedCliReturnValue>::convert)
+    }
+}
+
+///
+/// This trait allows positional arguments to be added to the schema
+///
+/// Like [`IntoSchema`], it's implemented for `CliPositional` types and tuples of them
+pub trait IntoPositionals {
+    fn add_positionals(schema: &mut Schema) -> Result<()>;
+
+    /// Converts the values stored while parsing into the types of the positional arguments
+    fn convert(args: &mut CliArgs) -> std::result::Result<(), CallError>;
+}
+
+impl<P1, P2> IntoPositionals for (P1, P2)
+where
+    P1: IntoPositionals,
+    P2: IntoPositionals,
+{
+    fn add_positionals(schema: &mut Schema) -> Result<()> {
+        P1::add_positionals(schema)?;
+        P2::add_positionals(schema)
+    }
+
+    fn convert(args: &mut CliArgs) -> std::result::Result<(), CallError> {
+        P1::convert(args)?;
+        P2::convert(args)
+    }
+}
+
+impl<P> IntoPositionals for P
+where
+    P: CliPositional,
+{
+    fn add_positionals(schema: &mut Schema) -> Result<()> {
+        schema.add_positional(SchemaPositional {
+            kind: <P::Content as CliReturnValue>::KIND,
+            name: P::name(),
+            arity: P::arity(),
+            description: P::description(),
+        })
+    }
+
+    fn convert(args: &mut CliArgs) -> std::result::Result<(), CallError> {
+        args.convert(P::name(), <P::Content as SealedCliReturnValue>::convert)
+            .map_err(|err| err.for_positional(P::name()))
+    }
+}
+
+///
+/// This trait allows subcommands to be added to the schema
+///
+/// Like [`IntoSchema`], it's implemented for `CliCommand` types and tuples of them
+pub trait IntoCommands {
+    fn add_commands(schema: &mut Schema) -> Result<()>;
+
+    /// Converts the values of the subcommand `name`, if it's one of these subcommands
+    fn convert(name: &str, args: &mut CliArgs) -> std::result::Result<(), CallError>;
+}
+
+impl<C1, C2> IntoCommands for (C1, C2)
+where
+    C1: IntoCommands,
+    C2: IntoCommands,
+{
+    fn add_commands(schema: &mut Schema) -> Result<()> {
+        C1::add_commands(schema)?;
+        C2::add_commands(schema)
+    }
+
+    fn convert(name: &str, args: &mut CliArgs) -> std::result::Result<(), CallError> {
+        C1::convert(name, args)?;
+        C2::convert(name, args)
+    }
+}
+
+impl<C> IntoCommands for C
+where
+    C: CliCommand,
+{
+    fn add_commands(schema: &mut Schema) -> Result<()> {
+        schema.add_subcommand(Subcommand {
+            name: C::name(),
+            description: C::description(),
+            schema: Schema::create::<C::Args>()?,
+        })
+    }
+
+    fn convert(name: &str, args: &mut CliArgs) -> std::result::Result<(), CallError> {
+        if name == C::name() {
+            C::Args::convert(args)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 #[cfg(test)]
@@ -128,6 +430,47 @@ mod test {
     arg!(Force: "force", 'f' -> bool);
     arg!(SetUpstream: "set-upstream" -> String);
     arg!(OutFile2: "output", 'o' -> String);
+    arg!(Jobs: "jobs", 'j' -> usize = 4);
+    arg!(BadJobs: "jobs", 'j' -> usize = "many");
+
+    // `arg!` already rejects this at compile time
+    struct ForceDefault;
+
+    impl crate::CliArg for ForceDefault {
+        type Content = bool;
+
+        fn long() -> &'static str {
+            "force"
+        }
+
+        fn short() -> Option<char> {
+            None
+        }
+
+        fn default_value() -> Option<&'static str> {
+            Some("true")
+        }
+    }
+
+    struct Even;
+
+    impl std::str::FromStr for Even {
+        type Err = String;
+
+        fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+            match s.parse::<usize>() {
+                Ok(number) if number % 2 == 0 => Ok(Even),
+                _ => Err("not an even number".to_string()),
+            }
+        }
+    }
+
+    impl crate::CliValue for Even {
+        const NAME: &'static str = "even";
+    }
+
+    arg!(EvenJobs: "jobs" -> Even = 4);
+    arg!(OddJobs: "jobs" -> Even = 3);
 
     #[test]
     fn one_command_schema() {
@@ -136,6 +479,10 @@ mod test {
             kind: SchemaKind::String,
             long: "output",
             short: Some('o'),
+            default: None,
+            required: false,
+            description: None,
+            env: None,
         };
         assert_eq!(schema.longs.get("output"), Some(&out_file));
         assert_eq!(schema.shorts.get(&'o'), Some(&out_file));
@@ -149,11 +496,19 @@ mod test {
             kind: SchemaKind::String,
             long: "output",
             short: Some('o'),
+            default: None,
+            required: false,
+            description: None,
+            env: None,
         };
         let force = SchemaCommand {
             kind: SchemaKind::Bool,
             long: "force",
             short: Some('f'),
+            default: None,
+            required: false,
+            description: None,
+            env: None,
         };
 
         assert_eq!(schema.longs.get("output"), Some(&out_file));
@@ -172,16 +527,28 @@ mod test {
             kind: SchemaKind::String,
             long: "output",
             short: Some('o'),
+            default: None,
+            required: false,
+            description: None,
+            env: None,
         };
         let force = SchemaCommand {
             kind: SchemaKind::Bool,
             long: "force",
             short: Some('f'),
+            default: None,
+            required: false,
+            description: None,
+            env: None,
         };
         let set_upstream = SchemaCommand {
             kind: SchemaKind::String,
             long: "set-upstream",
             short: None,
+            default: None,
+            required: false,
+            description: None,
+            env: None,
         };
 
         assert_eq!(schema.longs.get("output"), Some(&out_file));
@@ -200,4 +567,87 @@ mod test {
         let schema = Schema::create::<(OutFile, OutFile2)>();
         assert!(schema.is_err());
     }
+
+    #[test]
+    fn default_value() {
+        let schema = Schema::create::<Jobs>().unwrap();
+        let jobs = SchemaCommand {
+            kind: SchemaKind::UInt,
+            long: "jobs",
+            short: Some('j'),
+            default: Some("4"),
+            required: false,
+            description: None,
+            env: None,
+        };
+        assert_eq!(schema.longs.get("jobs"), Some(&jobs));
+    }
+
+    #[test]
+    fn invalid_default_value() {
+        assert!(Schema::create::<BadJobs>().is_err());
+        assert!(Schema::create::<ForceDefault>().is_err());
+        assert!(Schema::create::<EvenJobs>().is_ok());
+        assert!(Schema::create::<OddJobs>().is_err());
+    }
+
+    crate::positional!(Input: "input" -> String);
+    crate::positional!(Inputs: "inputs" -> Vec<String>, many);
+    crate::positional!(BadInputs: "inputs" -> String, many);
+    crate::positional!(Flag: "flag" -> bool);
+    crate::positional!(Output: "output" -> String);
+
+    #[test]
+    fn positionals() {
+        let schema = Schema::create::<(Force, crate::Positionals<(Input, Inputs)>)>().unwrap();
+        let names = schema
+            .positionals()
+            .iter()
+            .map(|positional| positional.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["input", "inputs"]);
+
+        assert!(Schema::create::<crate::Positionals<BadInputs>>().is_err());
+        assert!(Schema::create::<crate::Positionals<Flag>>().is_err());
+        assert!(Schema::create::<(OutFile, crate::Positionals<Output>)>().is_err());
+        assert!(Schema::create::<(crate::Positionals<Output>, OutFile)>().is_err());
+        assert!(
+            Schema::create::<(crate::Positionals<Input>, crate::Subcommands<Build>)>().is_err()
+        );
+    }
+
+    crate::command!(Build: "build" -> Force);
+    crate::command!(Build2: "build" -> OutFile);
+
+    #[test]
+    fn subcommands() {
+        let schema =
+            Schema::create::<(Force, crate::Subcommands<(Build, SetUpstreamCommand)>)>().unwrap();
+        let names = schema
+            .subcommands()
+            .iter()
+            .map(|subcommand| subcommand.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["build", "set-upstream"]);
+        assert!(schema
+            .subcommand("build")
+            .unwrap()
+            .schema
+            .long("force")
+            .is_some());
+
+        assert!(Schema::create::<crate::Subcommands<(Build, Build2)>>().is_err());
+    }
+
+    crate::command!(SetUpstreamCommand: "set-upstream" -> (SetUpstream, ()));
+
+    #[test]
+    fn declaration_order() {
+        let schema = Schema::create::<((SetUpstream, Force), OutFile)>().unwrap();
+        let longs = schema
+            .arguments()
+            .map(|command| command.long)
+            .collect::<Vec<_>>();
+        assert_eq!(longs, ["set-upstream", "force", "output"]);
+    }
 }
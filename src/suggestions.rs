@@ -0,0 +1,87 @@
+//!
+//! Finds the arguments the user probably meant when they made a typo
+
+/// The maximum amount of suggestions returned
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns the candidates that are close to `typed`, the closest first
+pub fn similar<'a>(typed: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    if typed.is_empty() {
+        return Vec::new();
+    }
+
+    let max_distance = (typed.chars().count() / 3).max(1);
+
+    let mut similar = candidates
+        .filter_map(|candidate| {
+            let distance = distance(typed, candidate);
+            if distance <= max_distance || candidate.starts_with(typed) {
+                Some((distance, candidate))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // the sort is stable, so equally close candidates stay in declaration order
+    similar.sort_by_key(|&(distance, _)| distance);
+    similar
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// The levenshtein distance between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            let insertion = current[j] + 1;
+            let deletion = previous[j + 1] + 1;
+            current[j + 1] = substitution.min(insertion).min(deletion);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        assert_eq!(distance("output", "output"), 0);
+        assert_eq!(distance("ouput", "output"), 1);
+        assert_eq!(distance("outptu", "output"), 2);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn typo() {
+        let candidates = ["force", "output", "optimize"];
+        assert_eq!(similar("ouput", candidates.iter().copied()), ["output"]);
+        assert_eq!(similar("forse", candidates.iter().copied()), ["force"]);
+    }
+
+    #[test]
+    fn prefix() {
+        let candidates = ["force", "output", "optimize"];
+        assert_eq!(similar("opt", candidates.iter().copied()), ["optimize"]);
+    }
+
+    #[test]
+    fn nothing_similar() {
+        let candidates = ["force", "output", "optimize"];
+        assert!(similar("verbose", candidates.iter().copied()).is_empty());
+        assert!(similar("", candidates.iter().copied()).is_empty());
+    }
+}